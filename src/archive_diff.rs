@@ -0,0 +1,136 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use zip::ZipArchive;
+
+/// A difference between two archives, see [`diff_archives`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryChange {
+    /// An entry only the new archive has.
+    Added(String),
+    /// An entry only the old archive has.
+    Removed(String),
+    /// An entry whose CRC or size changed.
+    Modified(String),
+}
+
+impl EntryChange {
+    /// The name of the entry.
+    pub fn name(&self) -> &str {
+        match self {
+            EntryChange::Added(name) | EntryChange::Removed(name) | EntryChange::Modified(name) => {
+                name
+            }
+        }
+    }
+}
+
+impl fmt::Display for EntryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryChange::Added(name) => write!(f, "added: {}", name),
+            EntryChange::Removed(name) => write!(f, "removed: {}", name),
+            EntryChange::Modified(name) => write!(f, "modified: {}", name),
+        }
+    }
+}
+
+/// CRC and size of every file entry, by name, from the central directory.
+pub(crate) fn entry_digests(path: &Path) -> Result<BTreeMap<String, (u32, u64)>> {
+    let file = File::open(path).with_context(|| format!("Failed to open archive: {:?}", path))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read archive: {:?}", path))?;
+
+    let mut entries = BTreeMap::new();
+    for index in 0..archive.len() {
+        // Raw access reads the recorded CRC without decompressing
+        let entry = archive.by_index_raw(index)?;
+        if !entry.is_dir() {
+            entries.insert(entry.name().to_string(), (entry.crc32(), entry.size()));
+        }
+    }
+    Ok(entries)
+}
+
+/// Compares the file entries of two zip archives by name, CRC and size,
+/// without extracting them.
+///
+/// Returns the differences sorted by name; an empty list means both archives
+/// hold the same content. Directory entries are left out.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::diff_archives;
+/// use std::path::Path;
+///
+/// for change in diff_archives(Path::new("v1.zip"), Path::new("v2.zip")).unwrap() {
+///     println!("{}", change); // modified: src/main.rs
+/// }
+/// ```
+pub fn diff_archives(old: &Path, new: &Path) -> Result<Vec<EntryChange>> {
+    let old_entries = entry_digests(old)?;
+    let new_entries = entry_digests(new)?;
+
+    let mut changes = Vec::new();
+    for (name, entry) in &new_entries {
+        match old_entries.get(name) {
+            None => changes.push(EntryChange::Added(name.clone())),
+            Some(old_entry) if old_entry != entry => {
+                changes.push(EntryChange::Modified(name.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in old_entries
+        .keys()
+        .filter(|name| !new_entries.contains_key(*name))
+    {
+        changes.push(EntryChange::Removed(name.clone()));
+    }
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_diff_classifies_changes() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+        std::fs::write(root.join("CHANGELOG.md"), b"0.1.0").unwrap();
+
+        let pack = |name: &str| {
+            let output = temp_dir.path().join(name);
+            let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+            pack_files(&files, &PackConfig::new(&root, &output), |_, _, _| {}).unwrap();
+            output
+        };
+        let old = pack("v1.zip");
+
+        std::fs::write(root.join("src/main.rs"), b"fn main() { run() }").unwrap();
+        std::fs::write(root.join("src/lib.rs"), b"pub fn run() {}").unwrap();
+        std::fs::remove_file(root.join("CHANGELOG.md")).unwrap();
+        let new = pack("v2.zip");
+
+        assert_eq!(
+            diff_archives(&old, &new).unwrap(),
+            vec![
+                EntryChange::Removed(String::from("CHANGELOG.md")),
+                EntryChange::Added(String::from("src/lib.rs")),
+                EntryChange::Modified(String::from("src/main.rs")),
+            ]
+        );
+        assert!(diff_archives(&new, &new).unwrap().is_empty());
+    }
+}
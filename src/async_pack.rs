@@ -0,0 +1,107 @@
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+use crate::{pack_files, PackConfig, PackSummary};
+
+/// A progress update sent by [`pack_files_async`] after each packed file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackProgress {
+    /// The file that was just packed.
+    pub path: PathBuf,
+    /// The size of that file in bytes.
+    pub file_size: u64,
+    /// The total number of bytes packed so far.
+    pub total_size: u64,
+}
+
+/// Async variant of [`pack_files`] for use inside tokio services.
+///
+/// The blocking packing work runs on tokio's blocking thread pool
+/// (`spawn_blocking`), so the async runtime stays responsive. Progress is
+/// streamed through `progress` instead of a callback; the channel closes once
+/// packing is over.
+///
+/// The channel is bounded: keep receiving while the returned future runs
+/// (e.g. with `tokio::join!`), otherwise packing pauses once it is full.
+/// Dropping the receiver is fine, progress updates are then discarded.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files_async, scan_files, PackConfig, PackProgress, ScanConfig};
+/// use tokio::sync::mpsc;
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let files = scan_files(&ScanConfig::new(".", vec![]))?;
+/// let config = PackConfig::new(".", "backup.zip");
+/// let (tx, mut rx) = mpsc::channel::<PackProgress>(64);
+///
+/// let report = async {
+///     while let Some(progress) = rx.recv().await {
+///         println!("Packed {:?}", progress.path);
+///     }
+/// };
+/// let (result, _) = tokio::join!(pack_files_async(files, config, tx), report);
+/// result?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn pack_files_async(
+    files: Vec<PathBuf>,
+    config: PackConfig,
+    progress: mpsc::Sender<PackProgress>,
+) -> Result<PackSummary> {
+    tokio::task::spawn_blocking(move || {
+        pack_files(&files, &config, |path, file_size, total_size| {
+            // A dropped receiver only means nobody is listening anymore
+            let _ = progress.blocking_send(PackProgress {
+                path: path.clone(),
+                file_size,
+                total_size,
+            });
+        })
+    })
+    .await
+    .context("Packing task failed")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_files, ScanConfig};
+    use std::fs::File;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[tokio::test]
+    async fn test_pack_files_async_streams_progress() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let output_zip_path = temp_dir.path().join("async.zip");
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let config = PackConfig::new(&root, &output_zip_path);
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let collect = async {
+            let mut updates = Vec::new();
+            while let Some(progress) = rx.recv().await {
+                updates.push(progress);
+            }
+            updates
+        };
+
+        let (result, updates) = tokio::join!(pack_files_async(files, config, tx), collect);
+        result.expect("Async packing failed");
+
+        assert_eq!(updates.len(), 2, "Expected one progress message per file");
+        assert_eq!(updates.last().unwrap().total_size, 12 + 7);
+
+        let archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+    }
+}
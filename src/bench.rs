@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Cursor, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Size and time of compressing the sample at one level, see [`sweep_levels`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelResult {
+    pub level: i64,
+    /// Size of the compressed sample, in bytes.
+    pub compressed_size: u64,
+    pub elapsed: Duration,
+}
+
+/// The levels worth trying for a compression method, `None` if it has none.
+pub fn level_range(method: CompressionMethod) -> Option<RangeInclusive<i64>> {
+    match method {
+        CompressionMethod::Zstd => Some(1..=22),
+        CompressionMethod::Deflated => Some(1..=9),
+        _ => None,
+    }
+}
+
+/// Reads up to `max_bytes` of the given files into one sample buffer.
+///
+/// Files are read in order until the sample is full; unreadable files and
+/// directories are skipped.
+pub fn read_sample(files: &[PathBuf], max_bytes: u64) -> Vec<u8> {
+    let mut sample = Vec::new();
+    for path in files {
+        let remaining = max_bytes.saturating_sub(sample.len() as u64);
+        if remaining == 0 {
+            break;
+        }
+        if let Ok(file) = File::open(path) {
+            let _ = file.take(remaining).read_to_end(&mut sample);
+        }
+    }
+    sample
+}
+
+/// Compresses the sample at every level of [`level_range`], one entry per level.
+///
+/// # Example
+///
+/// ```
+/// use srcpack::sweep_levels;
+/// use zip::CompressionMethod;
+///
+/// let sample = b"fn main() {}\n".repeat(1000);
+/// for result in sweep_levels(&sample, CompressionMethod::Deflated).unwrap() {
+///     println!("{}: {} bytes in {:?}", result.level, result.compressed_size, result.elapsed);
+/// }
+/// ```
+pub fn sweep_levels(sample: &[u8], method: CompressionMethod) -> Result<Vec<LevelResult>> {
+    let levels = level_range(method)
+        .with_context(|| format!("{} has no compression levels to compare", method))?;
+    levels
+        .map(|level| {
+            let options = SimpleFileOptions::default()
+                .compression_method(method)
+                .compression_level(Some(level));
+
+            let start = Instant::now();
+            let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+            zip.start_file("sample", options)?;
+            zip.write_all(sample)?;
+            let compressed = zip.finish()?.into_inner();
+
+            Ok(LevelResult {
+                level,
+                compressed_size: compressed.len() as u64,
+                elapsed: start.elapsed(),
+            })
+        })
+        .collect()
+}
+
+/// Picks the highest level whose sample compression finished within `budget`.
+pub fn pick_level(results: &[LevelResult], budget: Duration) -> Option<i64> {
+    results
+        .iter()
+        .filter(|result| result.elapsed <= budget)
+        .map(|result| result.level)
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_sweep_and_pick() {
+        let sample: Vec<u8> = (0..20_000)
+            .flat_map(|i| {
+                format!("let value_{} = compute({}, \"srcpack\");\n", i % 300, i % 7).into_bytes()
+            })
+            .collect();
+
+        let results = sweep_levels(&sample, CompressionMethod::Deflated).unwrap();
+        assert_eq!(
+            results.iter().map(|r| r.level).collect::<Vec<_>>(),
+            (1..=9).collect::<Vec<_>>()
+        );
+
+        // Every level compresses well, and higher levels don't produce
+        // (noticeably) larger output than the fastest one
+        let fastest = results[0].compressed_size;
+        for result in &results {
+            assert!(result.compressed_size < sample.len() as u64 / 4);
+            assert!(result.compressed_size <= fastest + fastest / 100);
+        }
+        assert!(results[8].compressed_size <= fastest);
+        assert!(sweep_levels(&sample, CompressionMethod::Stored).is_err());
+
+        // The highest level within the budget wins
+        let timed = |level, millis| LevelResult {
+            level,
+            compressed_size: 0,
+            elapsed: Duration::from_millis(millis),
+        };
+        let results = vec![timed(1, 10), timed(5, 40), timed(9, 200)];
+        assert_eq!(pick_level(&results, Duration::from_millis(50)), Some(5));
+        assert_eq!(pick_level(&results, Duration::from_millis(5)), None);
+    }
+}
@@ -1,9 +1,28 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
-use srcpack::{PackConfig, ScanConfig, pack_files, scan_files};
-use std::path::PathBuf;
-use std::time::Duration;
+use clap::{Parser, Subcommand};
+use indicatif::{HumanDuration, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use srcpack::{
+    bytes_eta, check_lockfile, content_address, copy_tree, default_output_stem, diff_archives,
+    entry_names, explain_path, extract_archive, file_count_warning, find_case_collisions,
+    find_duplicate_names, find_long_names, find_skipped, find_skipped_in_roots, find_sparse_files,
+    is_root_list, is_up_to_date, limit_files, manifest_path_for, ndjson_progress_line,
+    needs_confirmation, output_path_for_format, pack_files_multi, pack_files_split_by_dir,
+    pack_files_to_command, pack_from_tar, parse_compression, parse_modified_time, pick_level,
+    plan_extract, read_root_list, read_sample, refresh_rate_hz, repack_archive, scan_files,
+    scan_files_with_progress, scan_roots, snapshot_files, sweep_levels, temp_output_path,
+    top_level_breakdown, verify_archive_digests, write_csv_report, write_lockfile,
+    write_manifest_digests, ArchiveFormat, ArchiveMeta, Attachment, BrokenSymlinkPolicy,
+    ChecksumAlgorithm, DuplicatePolicy, EntryAction, EntryChange, ExtractConfig, HiddenPolicy,
+    LimitBy, LongNamePolicy, NameNorm, OutputNaming, OverwritePolicy, PackConfig, PackOrder,
+    PackStatus, Preset, Provenance, RunLog, ScanConfig, SkipReason, SpecialFilePolicy, Zip64Policy,
+    DEFAULT_MAX_EXTRACT_ENTRIES, DEFAULT_MAX_EXTRACT_SIZE, DEFAULT_MAX_NAME_LEN, DEFAULT_MAX_RATIO,
+    MAX_COMPONENT_LEN,
+};
+use std::collections::HashSet;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
 use zip::CompressionMethod;
 
 #[derive(Parser, Debug)]
@@ -13,36 +32,609 @@ use zip::CompressionMethod;
     version,
     about = "A fast CLI tool to pack source code respecting .gitignore",
     long_about = "srcpack is a utility to compress source code directories into zip files. \
-                  It automatically reads .gitignore files to exclude build artifacts like target/, node_modules/, etc."
+                  It automatically reads .gitignore files to exclude build artifacts like target/, node_modules/, etc.",
+    args_conflicts_with_subcommands = true
 )]
 struct Args {
-    /// Root directory to scan
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Root directory to scan, or a `.list` file naming several roots
     #[arg(default_value = ".")]
     path: PathBuf,
 
+    /// Read PATH as a list of roots (one per line), each packed under its
+    /// base name, whatever its extension
+    #[arg(long)]
+    path_is_list: bool,
+
     /// Output zip file path
     #[arg(short, long)]
     output: Option<PathBuf>,
 
+    /// Name the default output after the root directory, or always "archive"
+    /// [dir, fixed]
+    #[arg(
+        long,
+        default_value = "dir",
+        value_name = "SOURCE",
+        conflicts_with = "output"
+    )]
+    name_from: OutputNaming,
+
+    /// Pack into a new, uniquely named file in the system temp directory and
+    /// print only its absolute path to stdout, for the next step of a script
+    ///
+    /// The file is readable by the current user only (mode 0600 on unix).
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "output",
+            "pipe",
+            "split_by_dir",
+            "content_address",
+            "dry_run",
+            "only_newer_than_output"
+        ]
+    )]
+    temp_output: bool,
+
+    /// Print nothing but warnings and errors: no progress bars, no summary
+    #[arg(long, short = 'q')]
+    quiet: bool,
+
     /// Dry run: Scan and analyze files without creating a zip
     #[arg(long, short = 'd')]
     dry_run: bool,
 
+    /// Print the compression method of each file and what decided it
+    #[arg(long, short = 'v')]
+    verbose: bool,
+
     /// Show the top N the largest files (only works in dry-run mode or after compression)
     ///
     /// This option will list the largest files found to help you identify what is taking up space.
     #[arg(long, default_value_t = 0, requires = "dry_run")]
     top: usize,
 
+    /// Print the exact entry names the archive would contain (with --dry-run)
+    ///
+    /// Unlike the default listing, names include every path transformation
+    /// (e.g. --prefix, --attach destinations).
+    #[arg(long, requires = "dry_run")]
+    show_entries: bool,
+
+    /// Pack only a sample of N files (see --limit-by)
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Which files --limit keeps [first, largest, smallest]
+    #[arg(
+        long,
+        default_value = "first",
+        requires = "limit",
+        value_name = "ORDER"
+    )]
+    limit_by: LimitBy,
+
+    /// Archive formats to write, comma separated [zip, tar.gz]
+    ///
+    /// Several formats are written in one pass (e.g. "zip,tar.gz" produces
+    /// project.zip and project.tar.gz), reading each file only once.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "zip",
+        value_name = "FORMATS"
+    )]
+    format: Vec<ArchiveFormat>,
+
+    /// Place every entry under this top-level directory (e.g. "project-1.0")
+    #[arg(long, value_name = "DIR", value_parser = parse_prefix)]
+    prefix: Option<String>,
+
+    /// Remove this leading directory from every entry name (e.g. "project")
+    #[arg(long, value_name = "DIR", value_parser = parse_prefix)]
+    entry_prefix_strip: Option<String>,
+
+    /// Warn when the scan finds more than N files (0 disables the warning)
+    #[arg(long, default_value_t = 100_000, value_name = "N")]
+    warn_files: usize,
+
+    /// Fail when the scan finds more than N files
+    #[arg(long, value_name = "N")]
+    max_files: Option<usize>,
+
+    /// Skip sparse files (mostly holes, e.g. preallocated databases) larger than BYTES
+    #[arg(long, value_name = "BYTES")]
+    skip_sparse_over: Option<u64>,
+
+    /// Deflate sparse files even with --store (their holes read back as zeros)
+    #[arg(long)]
+    compress_sparse: bool,
+
+    /// Store files over BYTES as numbered .part0000 entries plus a .parts listing,
+    /// for upload endpoints that refuse large entries
+    #[arg(
+        long,
+        value_name = "BYTES",
+        conflicts_with_all = ["verify", "verify_against_source"]
+    )]
+    max_entry_size: Option<u64>,
+
+    /// Store files whose sampled entropy is above BITS per byte (0-8), such as
+    /// already compressed or encrypted data; 7.5 is a good start
+    #[arg(long, value_name = "BITS", value_parser = parse_entropy)]
+    entropy_threshold: Option<f64>,
+
+    /// Record each file's modification time to the nanosecond in a
+    /// SRCPACK_MTIMES.json entry, restored by `extract --restore-mtimes`
+    #[arg(long)]
+    embed_mtimes: bool,
+
+    /// Normalize entry names [none, lowercase, nfc]
+    #[arg(long, default_value = "none", value_name = "NORM")]
+    normalize_names: NameNorm,
+
+    /// What to do with entry names over --max-name-len bytes, or with a
+    /// component over --max-name-bytes [warn, error, truncate]
+    ///
+    /// "truncate" shortens them with a hash suffix and lists the full names in
+    /// the archive comment.
+    #[arg(long, default_value = "warn", value_name = "POLICY")]
+    long_names: LongNamePolicy,
+
+    /// What to do with files that end up with the same entry name (e.g. after
+    /// --normalize-names) [error, rename]
+    ///
+    /// "rename" adds a ~2, ~3, ... suffix before the extension of later ones.
+    #[arg(long, default_value = "error", value_name = "POLICY")]
+    duplicate_names: DuplicatePolicy,
+
+    /// Longest entry name in bytes
+    #[arg(long, default_value_t = DEFAULT_MAX_NAME_LEN, value_name = "BYTES")]
+    max_name_len: usize,
+
+    /// Longest path component in UTF-8 bytes, as most filesystems allow
+    #[arg(long, default_value_t = MAX_COMPONENT_LEN, value_name = "BYTES")]
+    max_name_bytes: usize,
+
+    /// Append timestamped lines for the scan, each packed file and the
+    /// summary to this file, for unattended runs
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Cap the read throughput at this many MB/s, to spare shared storage
+    #[arg(long, value_name = "MB/s", value_parser = parse_throttle)]
+    throttle: Option<u64>,
+
+    /// Record each file's uid/gid (unix), restored by `extract --preserve-ownership`
+    #[arg(long)]
+    preserve_ownership: bool,
+
+    /// Force-pack files matching a glob, even if ignored (e.g. "config/*.example")
+    #[arg(long, value_name = "GLOB")]
+    add: Vec<String>,
+
+    /// Only pack files tracked by git (drops untracked files, even if not ignored)
+    #[cfg(feature = "git")]
+    #[arg(long)]
+    tracked_only: bool,
+
+    /// Also pack the files tracked by git submodules (uninitialized ones are
+    /// skipped with a warning)
+    #[cfg(feature = "git")]
+    #[arg(long)]
+    include_submodules: bool,
+
+    /// Name entries from the root of the git repository instead of the scanned directory
+    #[cfg(feature = "git")]
+    #[arg(long, conflicts_with = "snapshot")]
+    git_relative: bool,
+
+    /// Skip paths marked `export-ignore` in .gitattributes, like `git archive`
+    #[arg(long)]
+    export_ignore: bool,
+
+    /// Skip directories containing this marker file, with everything below them
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = ".srcpackignore-dir"
+    )]
+    ignore_marker: Option<String>,
+
+    /// Ignore the .gitignore files of the directories above the scanned root
+    #[arg(long)]
+    no_parent_ignores: bool,
+
+    /// Only honor .gitignore files inside a git repository (a tree without
+    /// .git is packed as if it had none)
+    #[arg(long)]
+    gitignore_needs_git: bool,
+
+    /// Hidden (dot) entries to pack [include, exclude, files-only]
+    ///
+    /// "files-only" skips hidden directories like .github/ or .cache/ but keeps
+    /// dotfiles such as .gitignore and .env.
+    #[arg(long, default_value = "include", value_name = "POLICY")]
+    hidden: HiddenPolicy,
+
+    /// Keep directories whose only content is an excluded .gitkeep/.keep, as empty entries
+    #[arg(long)]
+    keep_empty_dirs: bool,
+
+    /// Stream the zip into the stdin of a shell command instead of writing a file
+    ///
+    /// e.g. --pipe "aws s3 cp - s3://bucket/backup.zip". The command's exit
+    /// status is checked.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        conflicts_with_all = ["output", "sfx", "stat_index", "manifest"]
+    )]
+    pipe: Option<String>,
+
     /// Manually exclude patterns (e.g. "*.mp4", "secrets/")
     #[arg(long, short = 'x')]
     exclude: Vec<String>,
 
+    /// Fail if a file matching this pattern would be packed (e.g. "*.pem", "id_rsa")
+    #[arg(long, value_name = "PATTERN")]
+    forbid: Vec<String>,
+
+    /// FIFOs, sockets and device files found by the scan [skip, error]
+    ///
+    /// They have no content to pack; "skip" leaves them out with a warning.
+    #[arg(long, default_value = "skip", value_name = "POLICY")]
+    special_files: SpecialFilePolicy,
+
+    /// Symlinks whose target is missing [skip, warn, error]
+    #[arg(long, default_value = "skip", value_name = "POLICY")]
+    broken_symlinks: BrokenSymlinkPolicy,
+
+    /// Skip directories holding more than N direct entries, with a warning
+    #[arg(long, value_name = "N")]
+    max_dir_entries: Option<usize>,
+
+    /// Only pack files modified at or after this date (YYYY-MM-DD, UTC) or
+    /// age (e.g. 7d, 12h, 2w)
+    #[arg(long, value_name = "DATE", value_parser = parse_modified_time)]
+    since: Option<SystemTime>,
+
+    /// Only pack files modified before this date (YYYY-MM-DD, UTC) or age
+    #[arg(long, value_name = "DATE", value_parser = parse_modified_time)]
+    until: Option<SystemTime>,
+
+    /// Exclude the cache and generated files of an ecosystem; repeatable
+    /// [node, python, rust, java, macos, windows]
+    #[arg(long, value_name = "NAME")]
+    preset: Vec<Preset>,
+
+    /// Also store directory entries, preserving directory permissions (e.g. a 0700 dir)
+    #[arg(long)]
+    dir_entries: bool,
+
+    /// Add an external file to the archive as SOURCE[:DEST] (e.g. "LICENSE:legal/LICENSE")
+    ///
+    /// The source does not need to be under the scanned directory. Without DEST,
+    /// the file is stored at the archive root under its own name.
+    #[arg(long, value_name = "SOURCE[:DEST]")]
+    attach: Vec<Attachment>,
+
+    /// Write a checksum manifest of every packed file next to the archive
+    ///
+    /// The sidecar is named after the archive and algorithm (e.g. `project.zip.sha256`).
+    #[arg(long)]
+    manifest: bool,
+
+    /// Hash algorithm used by the manifest and --verify [sha1, sha256, sha512, blake3]
+    #[arg(long, default_value = "sha256", value_name = "ALGORITHM")]
+    checksum_algorithm: ChecksumAlgorithm,
+
+    /// Create a self-extracting shell script instead of a plain zip (unix)
+    ///
+    /// The output is a runnable `.sh` file: `sh archive.sh [destination]` extracts it.
+    /// It remains a valid zip file for regular unzip tools.
+    #[arg(long)]
+    sfx: bool,
+
+    /// Skip packing when the output archive is newer than every scanned file
+    #[arg(long, conflicts_with = "pipe")]
+    only_newer_than_output: bool,
+
+    /// Pack even if --only-newer-than-output finds the archive up to date
+    #[arg(long, requires = "only_newer_than_output")]
+    force: bool,
+
+    /// Keep the setuid, setgid and sticky bits in the stored modes (tar.gz only;
+    /// they are cleared by default so extracting can't create a setuid binary)
+    #[arg(long)]
+    keep_special_bits: bool,
+
+    /// Add SRCPACK_SKIPPED.json to the archive, listing what was left out and why
+    #[arg(long, conflicts_with = "split_by_dir")]
+    embed_skip_report: bool,
+
+    /// Add SRCPACK_PROVENANCE.json to the archive: srcpack version, arguments,
+    /// compression, patterns and time of the run
+    #[arg(long)]
+    provenance: bool,
+
+    /// Add SRCPACK_META.txt as the first entry: creation time, host name and source root
+    #[arg(long)]
+    meta: bool,
+
+    /// Write a CSV of the packed files: path, size, compressed size, method and,
+    /// with --manifest or --verify, the digest
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pipe", "split_by_dir"])]
+    report: Option<PathBuf>,
+
+    /// Record the packed files with their SHA-256 and size in a lockfile (e.g. srcpack.lock)
+    #[arg(long, value_name = "PATH")]
+    lockfile: Option<PathBuf>,
+
+    /// Check the scanned files against a lockfile instead of packing; fails on any drift
+    #[arg(long, value_name = "PATH", conflicts_with = "lockfile")]
+    check_lock: Option<PathBuf>,
+
+    /// Skip files that can't be read instead of failing; exits with code 2 if any were
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Don't ask for confirmation before packing a large tree (50k files or 1 GB)
+    #[arg(long, short = 'y')]
+    yes: bool,
+
+    /// Name the archive after the BLAKE3 digest of its content: <digest>.<EXT>
+    ///
+    /// The archive is written to a temporary file in the output directory
+    /// (--output if it is a directory, else its parent), then renamed. EXT defaults
+    /// to the format's extension.
+    #[arg(
+        long,
+        value_name = "EXT",
+        num_args = 0..=1,
+        conflicts_with_all = ["pipe", "stat_index"]
+    )]
+    content_address: Option<Option<String>>,
+
+    /// Write one archive per top-level directory (archive-<dir>.zip, archive-root.zip)
+    #[arg(long, conflicts_with_all = ["pipe", "content_address", "attach", "manifest"])]
+    split_by_dir: bool,
+
+    /// Read the zip archive back once written and check every entry against the
+    /// digest of its file, taken while packing
+    #[arg(long, conflicts_with_all = ["pipe", "split_by_dir", "reuse_index"])]
+    verify: bool,
+
+    /// Like --verify, but compare every entry byte for byte with its file on
+    /// disk, read again after packing
+    #[arg(long, conflicts_with_all = ["pipe", "split_by_dir", "reuse_index", "verify"])]
+    verify_against_source: bool,
+
+    /// Write entries ordered by modification time, oldest first
+    #[arg(long)]
+    sort_entries_by_mtime: bool,
+
+    /// Store a file uncompressed if compressing it takes longer than SECONDS
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    max_compress_time: Option<Duration>,
+
+    /// When to write ZIP64 extensions [always, never, auto]
+    ///
+    /// "auto" only uses them for files of 4GB or more, keeping small archives
+    /// readable by old unzip tools.
+    #[arg(long, default_value = "auto", value_name = "POLICY")]
+    zip64: Zip64Policy,
+
+    /// Record each file's (device, inode, mtime, size) next to the archive
+    ///
+    /// The sidecar (e.g. `project.zip.srcpack-index`) can be passed to
+    /// --reuse-index by a later run.
+    #[arg(long)]
+    stat_index: bool,
+
+    /// Skip files unchanged since the run that wrote this stat index
+    ///
+    /// Unchanged files are copied from that run's archive without recompressing;
+    /// if the archive is gone, they are left out (incremental archive).
+    #[arg(long, value_name = "PATH")]
+    reuse_index: Option<PathBuf>,
+
+    /// Pack a delta: leave out files this zip archive already holds with the
+    /// same content, and list them in a SRCPACK_DELTA.json entry
+    #[arg(
+        long,
+        value_name = "BASE",
+        conflicts_with_all = ["reuse_index", "verify", "verify_against_source"]
+    )]
+    since_archive: Option<PathBuf>,
+
+    /// Pack from a hardlink snapshot of the tree, taken right after the scan
+    ///
+    /// Files replaced or deleted while packing (e.g. by a build or `git checkout`)
+    /// do not affect the archive. Falls back to packing the live tree when
+    /// hardlinks are not available.
+    #[arg(long)]
+    snapshot: bool,
+
+    /// Treat portability warnings (e.g. case-insensitive name collisions) as errors
+    #[arg(long)]
+    strict: bool,
+
+    /// How packing progress is reported [bar, ndjson, none]
+    ///
+    /// "ndjson" prints one JSON object per packed file to stderr, e.g.
+    /// {"path":"src/main.rs","size":1024,"total":4096}
+    #[arg(long, value_enum, default_value_t = ProgressFormat::Bar, value_name = "FORMAT")]
+    progress_format: ProgressFormat,
+
+    /// Redraw the progress bars at most once every MS milliseconds, 1 to 1000
+    /// (default: 50), e.g. 500 over SSH or on a slow terminal
+    #[arg(long, value_name = "MS", value_parser = parse_refresh)]
+    progress_refresh: Option<Duration>,
+
     /// Compression method
     #[command(flatten)]
     compression: CompressionArgs,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Explain why a file is or isn't packed (which rule decided its fate)
+    Why {
+        /// The file or directory to explain
+        path: PathBuf,
+
+        /// Root directory of the scan
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+
+        /// Exclude patterns, as given when packing (e.g. "*.mp4", "!keep.mp4")
+        #[arg(long, short = 'x')]
+        exclude: Vec<String>,
+
+        /// Force-include globs, as given when packing
+        #[arg(long, value_name = "GLOB")]
+        add: Vec<String>,
+    },
+
+    /// Extract an archive into a directory
+    Extract {
+        /// The archive to extract
+        archive: PathBuf,
+
+        /// Destination directory
+        #[arg(default_value = ".")]
+        dest: PathBuf,
+
+        /// Remove the first N path components of each entry, like tar
+        #[arg(long, default_value_t = 0, value_name = "N")]
+        strip_components: usize,
+
+        /// Restore the uid/gid recorded with --preserve-ownership (needs root)
+        #[arg(long)]
+        preserve_ownership: bool,
+
+        /// Restore the exact modification times recorded with --embed-mtimes
+        #[arg(long)]
+        restore_mtimes: bool,
+
+        /// What to do with files that already exist: skip, overwrite or
+        /// newer (overwrite only if the entry is more recent)
+        #[arg(long, default_value_t = OverwritePolicy::Overwrite, value_name = "POLICY")]
+        on_conflict: OverwritePolicy,
+
+        /// Refuse archives expanding to more than this many MB (0: no limit)
+        #[arg(long, value_name = "MB", default_value_t = DEFAULT_MAX_EXTRACT_SIZE / (1024 * 1024))]
+        max_size: u64,
+
+        /// Refuse archives with more entries than this (0: no limit)
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_EXTRACT_ENTRIES)]
+        max_entries: usize,
+
+        /// Refuse entries over 1 MB compressed more than N:1 (0: no limit)
+        #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_RATIO)]
+        max_ratio: u64,
+
+        /// List where each entry would go, flagging unsafe names and
+        /// overwrites, without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Compress a sample of the tree at every level, to pick one
+    Bench {
+        /// Root directory to sample
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Exclude patterns, as given when packing
+        #[arg(long, short = 'x')]
+        exclude: Vec<String>,
+
+        /// Compression method [deflated, zstd]
+        #[arg(long, default_value = "deflated", value_parser = parse_compression)]
+        compression: CompressionMethod,
+
+        /// Bytes of the scanned files used as the sample
+        #[arg(long, default_value_t = 8 * 1024 * 1024, value_name = "BYTES")]
+        sample_bytes: u64,
+
+        /// Pick the highest level that compresses the sample within SECONDS
+        #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+        pick_level: Option<Duration>,
+    },
+
+    /// Copy the files that would be packed into a directory, keeping their layout
+    Sync {
+        /// Destination directory
+        dest: PathBuf,
+
+        /// Root directory to copy from
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+
+        /// Exclude patterns, as given when packing
+        #[arg(long, short = 'x')]
+        exclude: Vec<String>,
+    },
+
+    /// Repackage a tar (or .tar.gz) as a filtered zip, without extracting it
+    FromTar {
+        /// The tar archive to read, "-" for stdin
+        input: PathBuf,
+
+        /// The zip archive to write
+        output: PathBuf,
+
+        /// Exclude patterns, matched against the entry names
+        #[arg(long, short = 'x')]
+        exclude: Vec<String>,
+    },
+
+    /// Compare two zip archives: list added, removed and modified entries
+    ///
+    /// Entries are compared by CRC and size from the central directories;
+    /// nothing is extracted.
+    ArchiveDiff {
+        /// The older archive
+        old: PathBuf,
+
+        /// The newer archive
+        new: PathBuf,
+    },
+
+    /// Recompress an existing zip with other settings (no source tree needed)
+    Repack {
+        /// The archive to read
+        input: PathBuf,
+
+        /// The archive to write
+        output: PathBuf,
+
+        /// Compression method [stored, deflated, zstd]
+        #[arg(long, default_value = "deflated", value_parser = parse_compression)]
+        compression: CompressionMethod,
+
+        /// Compression level (deflated: 0-9, zstd: 1-22)
+        #[arg(long)]
+        level: Option<i64>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ProgressFormat {
+    /// Interactive progress bar
+    Bar,
+    /// One JSON object per packed file on stderr
+    Ndjson,
+    /// No progress output
+    None,
+}
+
 #[derive(clap::Args, Debug)]
 #[group(required = false, multiple = false)] // 这一组参数互斥
 struct CompressionArgs {
@@ -59,115 +651,722 @@ struct CompressionArgs {
     best: bool,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn main() -> ExitCode {
+    let status = run().unwrap_or_else(|err| {
+        eprintln!("Error: {:?}", err);
+        PackStatus::Fatal
+    });
+    ExitCode::from(status.exit_code())
+}
+
+fn run() -> Result<PackStatus> {
+    let mut args = Args::parse();
+    if args.quiet {
+        args.progress_format = ProgressFormat::None;
+    }
+    // The archive path is the only thing a script reads from --temp-output
+    let quiet = args.quiet || args.temp_output;
+
+    match args.command {
+        Some(Command::Why {
+            path,
+            root,
+            exclude,
+            add,
+        }) => {
+            explain(&path, &root, exclude, add)?;
+            return Ok(PackStatus::Complete);
+        }
+        Some(Command::Bench {
+            path,
+            exclude,
+            compression,
+            sample_bytes,
+            pick_level,
+        }) => {
+            bench(&path, exclude, compression, sample_bytes, pick_level)?;
+            return Ok(PackStatus::Complete);
+        }
+        Some(Command::Sync {
+            dest,
+            root,
+            exclude,
+        }) => {
+            let root_path = std::fs::canonicalize(&root)
+                .with_context(|| format!("Cannot access directory: {:?}", root))?;
+            let files = scan_files(&ScanConfig::new(&root_path, exclude))?;
+            let written = copy_tree(&files, &dest, &root_path)?;
+            println!("✅ Copied {} files into {}", written.len(), dest.display());
+            return Ok(PackStatus::Complete);
+        }
+        Some(Command::FromTar {
+            input,
+            output,
+            exclude,
+        }) => {
+            let reader: Box<dyn std::io::Read> = match input.to_str() {
+                Some("-") => Box::new(std::io::stdin().lock()),
+                _ => Box::new(std::io::BufReader::new(
+                    std::fs::File::open(&input)
+                        .with_context(|| format!("Failed to open archive: {:?}", input))?,
+                )),
+            };
+            let name = input.to_string_lossy();
+            let reader: Box<dyn std::io::Read> =
+                match name.ends_with(".gz") || name.ends_with(".tgz") {
+                    true => Box::new(flate2::read::GzDecoder::new(reader)),
+                    false => reader,
+                };
+            let count = pack_from_tar(reader, &PackConfig::new("", &output), &exclude)?;
+            println!("✅ Packed {} entries into {}", count, output.display());
+            return Ok(PackStatus::Complete);
+        }
+        Some(Command::ArchiveDiff { old, new }) => {
+            let changes = diff_archives(&old, &new)?;
+            for change in &changes {
+                println!("  {}", change);
+            }
+            let count = |kind: fn(&EntryChange) -> bool| changes.iter().filter(|c| kind(c)).count();
+            println!(
+                "{} added, {} removed, {} modified",
+                count(|c| matches!(c, EntryChange::Added(_))),
+                count(|c| matches!(c, EntryChange::Removed(_))),
+                count(|c| matches!(c, EntryChange::Modified(_)))
+            );
+            return Ok(PackStatus::Complete);
+        }
+        Some(Command::Repack {
+            input,
+            output,
+            compression,
+            level,
+        }) => {
+            let count = repack_archive(&input, &output, compression, level)?;
+            println!(
+                "✅ Repacked {} entries into {} ({})",
+                count,
+                output.display(),
+                compression
+            );
+            return Ok(PackStatus::Complete);
+        }
+        Some(Command::Extract {
+            archive,
+            dest,
+            strip_components,
+            preserve_ownership,
+            restore_mtimes,
+            on_conflict,
+            max_size,
+            max_entries,
+            max_ratio,
+            dry_run,
+        }) => {
+            let mut config = ExtractConfig::new(archive, &dest);
+            config.strip_components = strip_components;
+            config.preserve_ownership = preserve_ownership;
+            config.restore_mtimes = restore_mtimes;
+            config.on_conflict = on_conflict;
+            config.max_total_size = Some(max_size.saturating_mul(1024 * 1024)).filter(|&n| n > 0);
+            config.max_entries = Some(max_entries).filter(|&n| n > 0);
+            config.max_ratio = Some(max_ratio).filter(|&n| n > 0);
+            if dry_run {
+                let plan = plan_extract(&config)?;
+                for entry in &plan {
+                    match &entry.dest {
+                        Some(path) => println!("  {:<9}  {}", entry.action, path.display()),
+                        None => println!(
+                            "  {:<9}  {} (escapes the destination)",
+                            entry.action, entry.name
+                        ),
+                    }
+                }
+                let count = |action| plan.iter().filter(|e| e.action == action).count();
+                println!(
+                    "\n{} entries: {} new, {} overwritten, {} skipped, {} rejected (dry run, nothing written)",
+                    plan.len(),
+                    count(EntryAction::Create),
+                    count(EntryAction::Overwrite),
+                    count(EntryAction::Skip),
+                    count(EntryAction::Reject)
+                );
+                return Ok(PackStatus::Complete);
+            }
+            let written = extract_archive(&config)?;
+            println!(
+                "✅ Extracted {} entries into {}",
+                written.len(),
+                dest.display()
+            );
+            return Ok(PackStatus::Complete);
+        }
+        None => {}
+    }
 
-    let root_path = std::fs::canonicalize(&args.path)
+    let mut root_path = std::fs::canonicalize(&args.path)
         .with_context(|| format!("Cannot access directory: {:?}", args.path))?;
+    let root_list = match args.path_is_list || is_root_list(&root_path) {
+        true => {
+            let roots = read_root_list(&root_path)?;
+            // The list's directory stands in for the root (e.g. for the output name)
+            root_path.pop();
+            Some(roots)
+        }
+        false => None,
+    };
+
+    let mut log = match &args.log_file {
+        Some(path) => Some(RunLog::create(path)?),
+        None => None,
+    };
+    if let Some(log) = log.as_mut() {
+        log.line(&format!("scan started: {}", root_path.display()));
+    }
 
     // --- Scanning ---
-    let scan_spinner = ProgressBar::new_spinner();
+    let scan_spinner = match args.progress_format {
+        ProgressFormat::Bar => {
+            ProgressBar::with_draw_target(None, draw_target(args.progress_refresh))
+        }
+        _ => ProgressBar::hidden(),
+    };
     scan_spinner.set_style(
         ProgressStyle::default_spinner()
             .template("{spinner:.green} {msg}")?
             .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏"),
     );
-    scan_spinner.set_message(format!(
-        "Scanning: {:?}",
-        root_path.file_name().unwrap_or_default()
+    let root_name = root_path.file_name().unwrap_or_default().to_os_string();
+    scan_spinner.set_message(format!("Scanning: {:?}", root_name));
+    let tick = Duration::from_millis(100).max(args.progress_refresh.unwrap_or_default());
+    scan_spinner.enable_steady_tick(tick);
+
+    // Entry names are decided the same way for --show-entries as for packing
+    let naming = naming_config(&args);
+
+    let mut config = ScanConfig::new(&root_path, args.exclude);
+    config.store_dir_entries = args.dir_entries;
+    config.warn_file_count = Some(args.warn_files).filter(|&n| n > 0);
+    config.max_files = args.max_files;
+    config.force_include = args.add;
+    config.export_ignore = args.export_ignore;
+    config.ignore_marker = args.ignore_marker;
+    config.keep_placeholder_dirs = args.keep_empty_dirs;
+    config.hidden = args.hidden;
+    config.parent_ignores = !args.no_parent_ignores;
+    config.gitignore_requires_repo = args.gitignore_needs_git;
+    config.presets = args.preset;
+    config.forbid_patterns = args.forbid;
+    config.special_files = args.special_files;
+    config.broken_symlinks = args.broken_symlinks;
+    config.max_dir_entries = args.max_dir_entries;
+    config.modified_after = args.since;
+    config.modified_before = args.until;
+    let mut root_attachments = Vec::new();
+    let files = match &root_list {
+        Some(roots) => {
+            root_attachments = scan_roots(roots, &config)?;
+            Vec::new()
+        }
+        None => scan_files_with_progress(&config, |phase, _, found| {
+            scan_spinner.set_message(format!("{}: {:?} ({} files)", phase, root_name, found));
+        })?,
+    };
+
+    scan_spinner.finish_with_message(format!(
+        "Found {} files.",
+        files.len() + root_attachments.len()
     ));
-    scan_spinner.enable_steady_tick(Duration::from_millis(100));
+    if let Some(log) = log.as_mut() {
+        log.line(&format!(
+            "scan finished: {} files",
+            files.len() + root_attachments.len()
+        ));
+    }
+
+    #[cfg(feature = "git")]
+    let files = match args.tracked_only {
+        true => srcpack::retain_tracked(files, &root_path)?,
+        false => files,
+    };
+    #[cfg(feature = "git")]
+    let files = match args.include_submodules {
+        true => srcpack::add_submodule_files(files, &root_path)?,
+        false => files,
+    };
 
-    let config = ScanConfig::new(&root_path, args.exclude);
-    let files = scan_files(&config)?;
+    if let Some(warning) = file_count_warning(files.len(), &config) {
+        eprintln!("⚠️  {}", warning);
+    }
 
-    scan_spinner.finish_with_message(format!("Found {} files.", files.len()));
+    let files = match args.limit {
+        Some(limit) if files.len() + root_attachments.len() > limit => {
+            let total = files.len() + root_attachments.len();
+            eprintln!(
+                "⚠️  --limit: keeping the {} {} of {} files. The archive is a partial sample!",
+                args.limit_by, limit, total
+            );
+            // The files of listed roots are sampled along with the scanned ones
+            let scanned = files.iter();
+            let listed = root_attachments.iter().map(|attachment| &attachment.source);
+            let kept: HashSet<PathBuf> = limit_files(
+                scanned.chain(listed).cloned().collect(),
+                limit,
+                args.limit_by,
+            )
+            .into_iter()
+            .collect();
+            root_attachments.retain(|attachment| kept.contains(&attachment.source));
+            files
+                .into_iter()
+                .filter(|path| kept.contains(path))
+                .collect()
+        }
+        _ => files,
+    };
+    // Packed under their own names: the files of listed roots, then --attach
+    let attachments: Vec<Attachment> = root_attachments
+        .iter()
+        .chain(&args.attach)
+        .cloned()
+        .collect();
+
+    // --- Portability Checks ---
+    let collisions = find_case_collisions(&files, &root_path, &attachments);
+    if !collisions.is_empty() {
+        eprintln!("\n⚠️  Names that collide on case-insensitive filesystems (macOS/Windows):");
+        for group in &collisions {
+            eprintln!("  {}", group.join(" <-> "));
+        }
+        if args.strict {
+            anyhow::bail!(
+                "Found {} case-insensitive name collision(s) (--strict)",
+                collisions.len()
+            );
+        }
+    }
+
+    // --- Sparse Files ---
+    let sparse = find_sparse_files(&files);
+    if !sparse.is_empty() {
+        eprintln!("\n⚠️  Sparse files, read in full (holes as zeros) when packing:");
+        for file in &sparse {
+            let skipped = args.skip_sparse_over.is_some_and(|limit| file.size > limit);
+            eprintln!(
+                "  {} ({} apparent, {} on disk){}",
+                file.path
+                    .strip_prefix(&root_path)
+                    .unwrap_or(&file.path)
+                    .display(),
+                format_size(file.size),
+                format_size(file.allocated),
+                if skipped { " - skipped" } else { "" }
+            );
+        }
+        if args.compress_sparse && args.compression.store {
+            eprintln!("  Note: sparse files are deflated despite --store (--compress-sparse)");
+        }
+    }
+    let files = match args.skip_sparse_over {
+        Some(limit) => {
+            let skipped: HashSet<&PathBuf> = sparse
+                .iter()
+                .filter(|file| file.size > limit)
+                .map(|file| &file.path)
+                .collect();
+            files
+                .into_iter()
+                .filter(|path| !skipped.contains(path))
+                .collect()
+        }
+        None => files,
+    };
+
+    // --- Skip Report ---
+    let skip_report = match args.embed_skip_report {
+        true => {
+            let mut skipped = match &root_list {
+                Some(roots) => find_skipped_in_roots(roots, &config, &root_attachments)?,
+                None => find_skipped(&config, &files)?,
+            };
+            for entry in &mut skipped {
+                let oversized = sparse.iter().find(|file| {
+                    args.skip_sparse_over.is_some_and(|limit| file.size > limit)
+                        && file.path.strip_prefix(&root_path).is_ok_and(|relative| {
+                            relative.to_string_lossy().replace('\\', "/") == entry.path
+                        })
+                });
+                if let Some(file) = oversized {
+                    entry.reason = SkipReason::Size;
+                    entry.detail = format!(
+                        "sparse file of {} (--skip-sparse-over)",
+                        format_size(file.size)
+                    );
+                }
+            }
+            Some(skipped)
+        }
+        false => None,
+    };
+
+    // Entries are named from the repository root with --git-relative, for the
+    // lockfile check as for packing
+    #[cfg(feature = "git")]
+    let name_root = match args.git_relative {
+        true => srcpack::git_root(&root_path)?,
+        false => root_path.clone(),
+    };
+    #[cfg(not(feature = "git"))]
+    let name_root = root_path.clone();
+
+    // --- Lockfile Check ---
+    if let Some(lock_path) = &args.check_lock {
+        let drift = check_lockfile(&files, &name_root, &attachments, lock_path)?;
+        if !drift.is_empty() {
+            eprintln!("\n❌ The files differ from {}:", lock_path.display());
+            for change in &drift {
+                eprintln!("  {}", change);
+            }
+            anyhow::bail!("{} file(s) drifted from the lockfile", drift.len());
+        }
+        println!("✅ The files match {}", lock_path.display());
+        return Ok(PackStatus::Complete);
+    }
 
     // --- Dry Run / Analysis Mode ---
     if args.dry_run {
         println!("\n--- Dry Run Mode (No Zip Created) ---");
 
-        let mut file_stats = Vec::with_capacity(files.len());
+        let mut file_stats = Vec::with_capacity(files.len() + attachments.len());
         let mut total_size: u64 = 0;
 
-        // Calculate sizes quickly
-        for file in &files {
-            let size = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        // Calculate sizes quickly. Attachments are shown under their entry names.
+        let scanned = files.iter().map(|file| {
+            let display_path = file.strip_prefix(&root_path).unwrap_or(file);
+            (file, display_path.display().to_string())
+        });
+        let attached = attachments
+            .iter()
+            .map(|attachment| (&attachment.source, attachment.entry_name.clone()));
+        for (file, display_path) in scanned.chain(attached) {
+            let size = std::fs::metadata(file)
+                .map(|m| if m.is_dir() { 0 } else { m.len() })
+                .unwrap_or(0);
             total_size += size;
-            file_stats.push((size, file));
+            file_stats.push((size, display_path));
         }
 
         // Print all files (standard behavior)
         // User can pipe this to 'more' or 'less'
-        if args.top == 0 {
-            for (_, file) in &file_stats {
-                let display_path = file.strip_prefix(&root_path).unwrap_or(file);
-                println!("{}", display_path.display());
+        if args.show_entries {
+            let entry_config = PackConfig {
+                root_path: name_root.clone(),
+                attachments: attachments.clone(),
+                ..naming.clone()
+            };
+            for name in entry_names(&files, &entry_config) {
+                println!("{}", name);
+            }
+        } else if args.top == 0 {
+            for (_, display_path) in &file_stats {
+                println!("{}", display_path);
             }
         }
 
         println!("\nTotal size: {}", format_size(total_size));
+        print_breakdown(&files, &root_path, &attachments);
 
         // If top is specified, show the analysis
         if args.top > 0 {
-            print_top_files(&mut file_stats, args.top, &root_path);
+            print_top_files(&mut file_stats, args.top);
         } else {
             println!("Tip: Use '--top 10' with '--dry-run' to see the largest files.");
         }
 
-        return Ok(());
+        return Ok(PackStatus::Complete);
     }
 
     // --- Compression Mode ---
     let output_path = match args.output {
         Some(p) => p,
         None => {
-            let dir_name = root_path
-                .file_name()
-                .unwrap_or_else(|| std::ffi::OsStr::new("archive"))
-                .to_string_lossy();
-            PathBuf::from(format!("{}.zip", dir_name))
+            let stem = default_output_stem(&root_path, args.name_from);
+            let extension = if args.sfx && args.format == [ArchiveFormat::Zip] {
+                "sh"
+            } else {
+                args.format[0].extension()
+            };
+            match args.temp_output {
+                true => temp_output_path(&stem, extension)?,
+                false => PathBuf::from(format!("{}.{}", stem, extension)),
+            }
         }
     };
 
-    let (method, level) = if args.compression.store {
-        (CompressionMethod::Stored, None)
+    if args.only_newer_than_output && !args.force {
+        let outputs: Vec<PathBuf> = match args.format.len() {
+            1 => vec![output_path.clone()],
+            _ => args
+                .format
+                .iter()
+                .map(|&format| output_path_for_format(&output_path, format))
+                .collect(),
+        };
+        // A previous archive inside the root is not an input of the next one
+        let resolved: Vec<PathBuf> = outputs
+            .iter()
+            .filter_map(|o| o.canonicalize().ok())
+            .collect();
+        let mut inputs: Vec<PathBuf> = files
+            .iter()
+            .filter(|path| !resolved.contains(path))
+            .cloned()
+            .collect();
+        inputs.extend(attachments.iter().map(|a| a.source.clone()));
+
+        if outputs.iter().all(|output| is_up_to_date(&inputs, output)) {
+            println!(
+                "✅ {} is up to date, nothing to pack (use --force to pack anyway).",
+                output_path.display()
+            );
+            return Ok(PackStatus::Complete);
+        }
+    }
+
+    // --- Confirmation for large packs ---
+    let interactive = std::io::stdin().is_terminal();
+    if interactive && !args.yes {
+        let total_size: u64 = files
+            .iter()
+            .chain(attachments.iter().map(|a| &a.source))
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .filter(|m| m.is_file())
+            .map(|m| m.len())
+            .sum();
+        let count = files.len() + attachments.len();
+        if needs_confirmation(count, total_size, interactive, args.yes)
+            && !confirm(&format!(
+                "Pack {} files ({})? [y/N] ",
+                count,
+                format_size(total_size)
+            ))?
+        {
+            println!("Aborted.");
+            return Ok(PackStatus::Complete);
+        }
+    }
+
+    let flag_compression = if args.compression.store {
+        Some((CompressionMethod::Stored, None))
     } else if args.compression.fast {
-        (CompressionMethod::Deflated, Some(1)) // Level 1 = Fastest Deflate
+        Some((CompressionMethod::Deflated, Some(1))) // Level 1 = Fastest Deflate
     } else if args.compression.best {
-        (CompressionMethod::Deflated, Some(9)) // Level 9 = Best Deflate
+        Some((CompressionMethod::Deflated, Some(9))) // Level 9 = Best Deflate
     } else {
-        (CompressionMethod::Deflated, Some(6)) // Default level
+        None
+    };
+
+    let snapshot = match args.snapshot {
+        true => {
+            let snapshot = snapshot_files(&files, &root_path)?;
+            if snapshot.is_none() {
+                eprintln!(
+                    "⚠️  --snapshot: hardlinks are not available here, packing the live tree."
+                );
+            }
+            snapshot
+        }
+        false => None,
+    };
+    let (pack_root, files) = match &snapshot {
+        Some(snapshot) => (snapshot.root().to_path_buf(), snapshot.files().to_vec()),
+        None => (name_root, files),
     };
 
-    let pack_config = PackConfig {
-        root_path: root_path.clone(),
-        output_path: output_path.clone(),
-        compression_method: method,
-        compression_level: level,
+    // Content-addressed archives get their name once written
+    let temp_output = match args.content_address {
+        Some(_) => {
+            if args.format.len() > 1 {
+                anyhow::bail!("--content-address needs a single --format");
+            }
+            let dir = match output_path.parent() {
+                _ if output_path.is_dir() => output_path.as_path(),
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let temp = tempfile::Builder::new()
+                .prefix(".srcpack-")
+                .suffix(".tmp")
+                .tempfile_in(dir)
+                .with_context(|| format!("Failed to create a temporary file in {:?}", dir))?;
+            Some(temp.into_temp_path())
+        }
+        None => None,
     };
 
-    println!("Compressing to: {:?}", output_path.file_name().unwrap());
-    if args.compression.store {
-        println!("Mode: Store (No Compression)");
-    } else if args.compression.fast {
-        println!("Mode: Fast Compression");
-    } else if args.compression.best {
-        println!("Mode: Best Compression");
+    let mut pack_config = PackConfig {
+        root_path: pack_root.clone(),
+        output_path: temp_output.as_deref().unwrap_or(&output_path).to_path_buf(),
+        attachments,
+        ..naming
+    };
+    // SRCPACK_COMPRESSION / SRCPACK_LEVEL, unless a flag says otherwise
+    pack_config.apply_env_defaults()?;
+    if let Some((method, level)) = flag_compression {
+        pack_config.compression_method = method;
+        pack_config.compression_level = level;
+    }
+    pack_config.sfx = args.sfx;
+    pack_config.zip64 = args.zip64;
+    pack_config.log_compression = args.verbose;
+    pack_config.strip_special_bits = !args.keep_special_bits;
+    // Hash while packing, so neither the manifest nor --verify reads the files again
+    if args.manifest || args.verify {
+        pack_config.hash_files = Some(args.checksum_algorithm);
+    }
+    pack_config.embed_skip_report = skip_report;
+    if args.provenance {
+        let mut provenance = Provenance::new(&config);
+        provenance.args = std::env::args().skip(1).collect();
+        provenance.timestamp = Some(std::time::SystemTime::now());
+        pack_config.provenance = Some(provenance);
+    }
+    if args.meta {
+        pack_config.meta = Some(ArchiveMeta::capture(&root_path));
+    }
+    pack_config.write_stat_index = args.stat_index;
+    pack_config.reuse_index = args.reuse_index;
+    pack_config.delta_base = args.since_archive;
+    pack_config.max_compress_time_per_file = args.max_compress_time;
+    pack_config.compress_sparse = args.compress_sparse;
+    pack_config.max_entry_size = args.max_entry_size;
+    pack_config.entropy_threshold = args.entropy_threshold;
+    pack_config.embed_mtimes = args.embed_mtimes;
+    pack_config.preserve_ownership = args.preserve_ownership;
+    pack_config.throttle = args.throttle;
+    pack_config.continue_on_error = args.keep_going;
+
+    let duplicates = find_duplicate_names(&files, &pack_config);
+    if !duplicates.is_empty() && args.duplicate_names == DuplicatePolicy::Rename {
+        eprintln!("\n⚠️  Files sharing an entry name, later ones get a ~N suffix:");
+        for duplicate in &duplicates {
+            let sources: Vec<String> = duplicate
+                .sources
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&pack_root)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                })
+                .collect();
+            eprintln!("  {}: {}", duplicate.entry_name, sources.join(", "));
+        }
+    }
+    if args.long_names == LongNamePolicy::Warn {
+        let long_names = find_long_names(&files, &pack_config);
+        if !long_names.is_empty() {
+            eprintln!(
+                "\n⚠️  {} entry name(s) over {} bytes or with a component over {} bytes, \
+                 some unzip tools may fail on them (see --long-names):",
+                long_names.len(),
+                args.max_name_len,
+                args.max_name_bytes
+            );
+            for name in &long_names {
+                eprintln!("  {}", name);
+            }
+        }
     }
 
-    println!("Compressing to: {:?}", output_path.file_name().unwrap());
+    if (args.verify || args.verify_against_source) && !args.format.contains(&ArchiveFormat::Zip) {
+        let flag = match args.verify_against_source {
+            true => "--verify-against-source",
+            false => "--verify",
+        };
+        anyhow::bail!("{} needs the zip format", flag);
+    }
+    if args.report.is_some() && !args.format.contains(&ArchiveFormat::Zip) {
+        anyhow::bail!("--report needs the zip format");
+    }
 
-    let bar = ProgressBar::new(files.len() as u64);
-    bar.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {percent}% (ETA: {eta}) {msg}",
-        )?
-        .progress_chars("##-"),
-    );
+    if !quiet {
+        match &args.pipe {
+            Some(command) => println!("Streaming to: {}", command),
+            None => println!("Compressing to: {:?}", output_path.file_name().unwrap()),
+        }
+        if args.compression.store {
+            println!("Mode: Store (No Compression)");
+        } else if args.compression.fast {
+            println!("Mode: Fast Compression");
+        } else if args.compression.best {
+            println!("Mode: Best Compression");
+        }
+    }
+
+    // Bytes give a far better ETA than the file count when sizes vary; the
+    // count is only used when no size could be read
+    let total_bytes: u64 = files
+        .iter()
+        .chain(pack_config.attachments.iter().map(|a| &a.source))
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum();
+    let entry_count = (files.len() + pack_config.attachments.len()) as u64;
+    let new_bar = || -> Result<ProgressBar> {
+        let (length, template) = match total_bytes {
+            0 => (
+                entry_count,
+                "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {percent}% (ETA: {eta}) {msg}",
+            ),
+            _ => (
+                total_bytes,
+                "[{elapsed_precise}] {bar:40.cyan/blue} {bytes:>10}/{total_bytes:10} {percent}% (ETA: {eta}) {msg}",
+            ),
+        };
+        let bar = match args.progress_format {
+            ProgressFormat::Bar => {
+                ProgressBar::with_draw_target(Some(length), draw_target(args.progress_refresh))
+            }
+            _ => ProgressBar::hidden(),
+        };
+        bar.set_style(
+            ProgressStyle::with_template(template)?
+                .with_key(
+                    "eta",
+                    |state: &ProgressState, w: &mut dyn std::fmt::Write| match bytes_eta(
+                        state.pos(),
+                        state.len().unwrap_or(0),
+                        state.elapsed(),
+                    ) {
+                        Some(eta) => write!(w, "{:#}", HumanDuration(eta)).unwrap(),
+                        None => write!(w, "-").unwrap(),
+                    },
+                )
+                .progress_chars("##-"),
+        );
+        Ok(bar)
+    };
+    let bar = new_bar()?;
+
+    let on_progress = |path_buf: &PathBuf, file_size, total_size| {
+        let relative_path = path_buf.strip_prefix(&pack_root).unwrap_or(path_buf);
+        if let Some(log) = log.as_mut() {
+            log.line(&format!(
+                "packed: {} ({} bytes)",
+                relative_path.display(),
+                file_size
+            ));
+        }
+
+        if args.progress_format == ProgressFormat::Ndjson {
+            eprintln!(
+                "{}",
+                ndjson_progress_line(relative_path, file_size, total_size)
+            );
+            return;
+        }
 
-    pack_files(&files, &pack_config, |path_buf, _, total_size| {
-        let relative_path = path_buf.strip_prefix(&root_path).unwrap_or(path_buf);
         let relative_path_str = relative_path.to_string_lossy().to_string();
 
         let display_name = truncate(&relative_path_str, 35);
@@ -178,30 +1377,376 @@ fn main() -> Result<()> {
             format_size(total_size)
         ));
 
-        bar.inc(1);
-    })?;
+        match total_bytes {
+            0 => bar.inc(1),
+            _ => bar.set_position(total_size),
+        }
+    };
+
+    let summary = match &args.pipe {
+        Some(command) => {
+            if args.format != [ArchiveFormat::Zip] {
+                anyhow::bail!("--pipe only streams the zip format");
+            }
+            pack_files_to_command(&files, &pack_config, command, on_progress)?
+        }
+        None if args.split_by_dir => {
+            pack_files_split_by_dir(&files, &pack_config, &args.format, on_progress)?
+        }
+        None => pack_files_multi(&files, &pack_config, &args.format, on_progress)?,
+    };
 
     bar.finish_with_message("Done!");
-    println!("\n✨ Success! Saved to: {}", output_path.display());
+    let mut outputs = summary.outputs;
+
+    if args.verify || args.verify_against_source {
+        let mut verify_config = pack_config.clone();
+        verify_config.output_path = outputs[args
+            .format
+            .iter()
+            .position(|&format| format == ArchiveFormat::Zip)
+            .unwrap_or(0)]
+        .clone();
+
+        let verify_bar = new_bar()?;
+        // Without digests, every entry is compared with its file
+        let digests = match args.verify_against_source {
+            true => &[][..],
+            false => &summary.digests[..],
+        };
+        verify_archive_digests(&files, &verify_config, digests, |phase, path, verified| {
+            let relative_path = path.strip_prefix(&pack_root).unwrap_or(path);
+            verify_bar.set_message(format!(
+                "{}: {} | Total: {}",
+                phase,
+                truncate(&relative_path.to_string_lossy(), 35),
+                format_size(verified)
+            ));
+            match total_bytes {
+                0 => verify_bar.inc(1),
+                _ => verify_bar.set_position(verified),
+            }
+        })?;
+        verify_bar.finish_with_message("Verified!");
+    }
+
+    if let (Some(extension), Some(temp_output)) = (&args.content_address, temp_output) {
+        let extension = extension.as_deref().unwrap_or(match args.sfx {
+            true => "sh",
+            false => args.format[0].extension(),
+        });
+        outputs = vec![content_address(&temp_output.keep()?, extension)?];
+    }
+
+    if summary.stored_fallbacks > 0 && !quiet {
+        println!(
+            "{} file(s) exceeded the compression time budget and were stored uncompressed.",
+            summary.stored_fallbacks
+        );
+    }
+
+    if args.verbose && !quiet {
+        println!("\nCompression per file:");
+        for decision in &summary.compression_log {
+            println!(
+                "  {:<9} {:<11} {}",
+                format!("{:?}", decision.method),
+                decision.reason,
+                decision
+                    .path
+                    .strip_prefix(&pack_root)
+                    .unwrap_or(&decision.path)
+                    .display()
+            );
+        }
+    }
+
+    if args.manifest {
+        let manifest_path = manifest_path_for(&outputs[0], args.checksum_algorithm);
+        write_manifest_digests(
+            &summary.digests,
+            &pack_root,
+            &manifest_path,
+            args.checksum_algorithm,
+        )?;
+        if !quiet {
+            println!(
+                "Manifest ({}) saved to: {}",
+                args.checksum_algorithm,
+                manifest_path.display()
+            );
+        }
+    }
+
+    if let Some(report_path) = &args.report {
+        let zip_output = &outputs[args
+            .format
+            .iter()
+            .position(|&format| format == ArchiveFormat::Zip)
+            .unwrap_or(0)];
+        write_csv_report(
+            zip_output,
+            &summary.digests,
+            args.checksum_algorithm,
+            report_path,
+        )?;
+        if !quiet {
+            println!("Report saved to: {}", report_path.display());
+        }
+    }
+
+    if let Some(lock_path) = &args.lockfile {
+        write_lockfile(&files, &pack_root, &pack_config.attachments, lock_path)?;
+        if !quiet {
+            println!("Lockfile saved to: {}", lock_path.display());
+        }
+    }
+
+    if args.temp_output {
+        for output in &outputs {
+            println!("{}", output.display());
+        }
+    } else if !quiet {
+        print_breakdown(&files, &pack_root, &pack_config.attachments);
+        match &args.pipe {
+            Some(command) => println!("\n✨ Success! Streamed to: {}", command),
+            None => {
+                let saved: Vec<String> = outputs.iter().map(|p| p.display().to_string()).collect();
+                println!("\n✨ Success! Saved to: {}", saved.join(", "));
+            }
+        }
+    }
+
+    if !summary.skipped.is_empty() {
+        eprintln!(
+            "\n⚠️  {} file(s) could not be read and were skipped (--keep-going):",
+            summary.skipped.len()
+        );
+        for skipped in &summary.skipped {
+            let path = skipped
+                .path
+                .strip_prefix(&pack_root)
+                .unwrap_or(&skipped.path);
+            eprintln!("  {}: {}", path.display(), skipped.error);
+        }
+    }
+
+    if let Some(mut log) = log {
+        for skipped in &summary.skipped {
+            log.line(&format!(
+                "skipped: {}: {}",
+                skipped.path.display(),
+                skipped.error
+            ));
+        }
+        let packed = entry_count as usize - summary.skipped.len();
+        log.summary(packed, summary.skipped.len(), &outputs);
+        log.finish()?;
+    }
+
+    Ok(match summary.skipped.is_empty() {
+        true => PackStatus::Complete,
+        false => PackStatus::Partial,
+    })
+}
+
+/// Builds the part of the pack configuration deciding entry names and their
+/// order, so `--dry-run --show-entries` lists the names packing writes. The
+/// root, output and attachments are left for the caller to fill in.
+fn naming_config(args: &Args) -> PackConfig {
+    let mut config = PackConfig::new("", "");
+    config.prefix = args.prefix.clone();
+    config.strip_entry_prefix = args.entry_prefix_strip.clone();
+    config.entry_name_normalization = args.normalize_names;
+    config.long_names = args.long_names;
+    config.duplicate_names = args.duplicate_names;
+    config.max_name_len = args.max_name_len;
+    config.max_component_len = args.max_name_bytes;
+    if args.sort_entries_by_mtime {
+        config.order = PackOrder::Mtime;
+    }
+    config
+}
+
+fn explain(
+    path: &PathBuf,
+    root: &PathBuf,
+    excludes: Vec<String>,
+    force_include: Vec<String>,
+) -> Result<()> {
+    let root_path = std::fs::canonicalize(root)
+        .with_context(|| format!("Cannot access directory: {:?}", root))?;
+    let target =
+        std::fs::canonicalize(path).with_context(|| format!("Cannot access path: {:?}", path))?;
+
+    let mut config = ScanConfig::new(&root_path, excludes);
+    config.force_include = force_include;
+    let verdict = explain_path(&config, &target)?;
+
+    let icon = if verdict.is_included() { "✅" } else { "🚫" };
+    let display_path = target.strip_prefix(&root_path).unwrap_or(&target);
+    println!("{} {}: {}", icon, display_path.display(), verdict);
+
+    Ok(())
+}
+
+/// Prints the size and time of compressing a sample of the tree at each level.
+fn bench(
+    path: &Path,
+    excludes: Vec<String>,
+    method: CompressionMethod,
+    sample_bytes: u64,
+    budget: Option<Duration>,
+) -> Result<()> {
+    let files = scan_files(&ScanConfig::new(path, excludes))?;
+    let sample = read_sample(&files, sample_bytes);
+    if sample.is_empty() {
+        anyhow::bail!("No readable files to sample under {:?}", path);
+    }
+
+    println!(
+        "Sample: {} from {} files, {}",
+        format_size(sample.len() as u64),
+        files.len(),
+        method
+    );
+    println!(
+        "{:>5}  {:>10}  {:>6}  {:>9}",
+        "Level", "Size", "Ratio", "Time"
+    );
+    let results = sweep_levels(&sample, method)?;
+    for result in &results {
+        println!(
+            "{:>5}  {:>10}  {:>5.1}%  {:>7.0}ms",
+            result.level,
+            format_size(result.compressed_size),
+            result.compressed_size as f64 * 100.0 / sample.len() as f64,
+            result.elapsed.as_secs_f64() * 1000.0
+        );
+    }
+
+    if let Some(budget) = budget {
+        match pick_level(&results, budget) {
+            Some(level) => println!("\n✅ Best level within {:?}: {}", budget, level),
+            None => println!("\n⚠️  No level compresses the sample within {:?}", budget),
+        }
+    }
 
     Ok(())
 }
 
-fn print_top_files(files: &mut Vec<(u64, &PathBuf)>, n: usize, root: &PathBuf) {
+/// Validates a --prefix value: a relative directory that stays inside the archive.
+fn parse_prefix(value: &str) -> Result<String> {
+    let prefix = value.replace('\\', "/").trim_matches('/').to_string();
+    if prefix.is_empty()
+        || prefix
+            .split('/')
+            .any(|part| matches!(part, "" | "." | ".."))
+    {
+        anyhow::bail!("Invalid prefix {:?}", value);
+    }
+    Ok(prefix)
+}
+
+/// Parses a positive number of seconds, fractions allowed (e.g. "0.5").
+fn parse_seconds(value: &str) -> Result<Duration> {
+    let seconds: f64 = value
+        .parse()
+        .with_context(|| format!("Invalid number of seconds: {:?}", value))?;
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|d| !d.is_zero())
+        .with_context(|| format!("Invalid number of seconds: {:?}", value))
+}
+
+/// Parses a --progress-refresh value, in milliseconds. Draw targets redraw
+/// at least once a second, so longer intervals are refused.
+fn parse_refresh(value: &str) -> Result<Duration> {
+    value
+        .parse()
+        .ok()
+        .filter(|ms| (1..=1000).contains(ms))
+        .map(Duration::from_millis)
+        .with_context(|| {
+            format!(
+                "Invalid refresh interval {:?} (expected 1 to 1000 ms)",
+                value
+            )
+        })
+}
+
+/// Parses an --entropy-threshold value, in bits per byte.
+fn parse_entropy(value: &str) -> Result<f64> {
+    value
+        .parse()
+        .ok()
+        .filter(|bits| (0.0..=8.0).contains(bits))
+        .with_context(|| format!("Invalid entropy threshold {:?} (expected 0 to 8)", value))
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> Result<bool> {
+    print!("{}", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Parses a --throttle value in MB/s into bytes per second.
+fn parse_throttle(value: &str) -> Result<u64> {
+    let megabytes: f64 = value
+        .parse()
+        .with_context(|| format!("Invalid throughput: {:?}", value))?;
+    let bytes = (megabytes * 1024.0 * 1024.0) as u64;
+    if !megabytes.is_finite() || bytes == 0 {
+        anyhow::bail!("Invalid throughput: {:?}", value);
+    }
+    Ok(bytes)
+}
+
+/// Where progress bars are drawn: stderr, at the rate of --progress-refresh.
+fn draw_target(refresh: Option<Duration>) -> ProgressDrawTarget {
+    match refresh {
+        Some(interval) => ProgressDrawTarget::stderr_with_hz(refresh_rate_hz(interval)),
+        None => ProgressDrawTarget::stderr(),
+    }
+}
+
+/// Prints how many files and bytes come from each top-level directory.
+fn print_breakdown(files: &[PathBuf], root: &Path, attachments: &[Attachment]) {
+    let breakdown = top_level_breakdown(files, root, attachments);
+    if breakdown.is_empty() {
+        return;
+    }
+
+    println!("\n📁 By top-level directory:");
+    for dir in &breakdown {
+        println!(
+            "  {:<24} {:>7} files  {:>10}",
+            truncate(&dir.name, 24),
+            dir.files,
+            format_size(dir.bytes)
+        );
+    }
+}
+
+fn print_top_files(files: &mut [(u64, String)], n: usize) {
     // Sort descending by size
-    files.sort_by(|a, b| b.0.cmp(&a.0));
+    files.sort_by_key(|&(size, _)| std::cmp::Reverse(size));
 
     let count = n.min(files.len());
 
     println!("\n📊 Largest {} files (Analysis):", count);
     println!("{:-<60}", "");
-    println!("{:<12} | {}", "Size", "File Path");
+    println!("{:<12} | File Path", "Size");
     println!("{:-<60}", "");
 
-    for i in 0..count {
-        let (size, path) = files[i];
-        let relative_path = path.strip_prefix(root).unwrap_or(path);
-        println!("{:<12} | {}", format_size(size), relative_path.display());
+    for (size, path) in files.iter().take(count) {
+        println!("{:<12} | {}", format_size(*size), path);
     }
     println!("{:-<60}", "");
 }
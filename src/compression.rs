@@ -0,0 +1,193 @@
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::CompressionMethod;
+
+use crate::{is_sparse, PackConfig};
+
+/// Why a file was written with its compression method, see [`compression_for`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionReason {
+    /// [`PackConfig::compression_method`] and level.
+    Default,
+    /// A sparse file deflated with [`PackConfig::compress_sparse`].
+    Sparse,
+    /// Stored because its sample is above [`PackConfig::entropy_threshold`].
+    Entropy,
+    /// Stored after compressing exceeded
+    /// [`PackConfig::max_compress_time_per_file`].
+    TimeBudget,
+}
+
+impl fmt::Display for CompressionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            CompressionReason::Default => "default",
+            CompressionReason::Sparse => "sparse",
+            CompressionReason::Entropy => "entropy",
+            CompressionReason::TimeBudget => "time-budget",
+        })
+    }
+}
+
+/// The compression method a file was written with, and why. Collected in
+/// [`PackSummary::compression_log`] with [`PackConfig::log_compression`].
+///
+/// [`PackSummary::compression_log`]: crate::PackSummary::compression_log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionDecision {
+    pub path: PathBuf,
+    pub method: CompressionMethod,
+    pub reason: CompressionReason,
+}
+
+/// Bytes read from the start of a file to estimate its entropy.
+const ENTROPY_SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// Picks the compression method and level of a file before it is written.
+///
+/// With [`PackConfig::entropy_threshold`], the start of the file is read to
+/// tell already compressed or encrypted data. The file can still end up
+/// `Stored` with [`CompressionReason::TimeBudget`] if compressing it takes
+/// too long.
+pub fn compression_for(
+    config: &PackConfig,
+    path: &Path,
+    metadata: &Metadata,
+) -> (CompressionMethod, Option<i64>, CompressionReason) {
+    if config.compress_sparse && is_sparse(metadata) {
+        return (CompressionMethod::Deflated, None, CompressionReason::Sparse);
+    }
+    if let Some(threshold) = config
+        .entropy_threshold
+        .filter(|_| config.compression_method != CompressionMethod::Stored)
+    {
+        if sample_entropy(path).is_some_and(|entropy| entropy > threshold) {
+            return (CompressionMethod::Stored, None, CompressionReason::Entropy);
+        }
+    }
+    (
+        config.compression_method,
+        config.compression_level,
+        CompressionReason::Default,
+    )
+}
+
+/// Shannon entropy, in bits per byte (0 to 8), of the start of a file.
+fn sample_entropy(path: &Path) -> Option<f64> {
+    let mut sample = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(ENTROPY_SAMPLE_BYTES)
+        .read_to_end(&mut sample)
+        .ok()?;
+    if sample.is_empty() {
+        return None;
+    }
+
+    let mut counts = [0u64; 256];
+    for &byte in &sample {
+        counts[byte as usize] += 1;
+    }
+    let len = sample.len() as f64;
+    Some(
+        counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / len;
+                -p * p.log2()
+            })
+            .sum(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_compression_for_reasons() {
+        let temp_dir = tempdir().unwrap();
+        let dense_path = temp_dir.path().join("dense.txt");
+        std::fs::write(&dense_path, b"fn main() {}").unwrap();
+        let dense = std::fs::metadata(&dense_path).unwrap();
+        let sparse_path = temp_dir.path().join("prealloc.db");
+        File::create(&sparse_path)
+            .unwrap()
+            .set_len(64 * 1024 * 1024)
+            .unwrap();
+        let sparse = std::fs::metadata(&sparse_path).unwrap();
+
+        let mut config = PackConfig::new(temp_dir.path(), "archive.zip");
+        config.compression_level = Some(9);
+        assert_eq!(
+            compression_for(&config, &dense_path, &dense),
+            (
+                CompressionMethod::Deflated,
+                Some(9),
+                CompressionReason::Default
+            )
+        );
+
+        config.compression_method = CompressionMethod::Stored;
+        config.compression_level = None;
+        assert_eq!(
+            compression_for(&config, &sparse_path, &sparse),
+            (CompressionMethod::Stored, None, CompressionReason::Default)
+        );
+
+        config.compress_sparse = true;
+        assert_eq!(
+            compression_for(&config, &dense_path, &dense),
+            (CompressionMethod::Stored, None, CompressionReason::Default)
+        );
+        // Some filesystems (e.g. without hole support) allocate every block
+        if is_sparse(&sparse) {
+            assert_eq!(
+                compression_for(&config, &sparse_path, &sparse),
+                (CompressionMethod::Deflated, None, CompressionReason::Sparse)
+            );
+        }
+    }
+
+    #[test]
+    fn test_entropy_threshold_stores_random_data() {
+        let temp_dir = tempdir().unwrap();
+        // xorshift output: every byte value about equally likely
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let random: Vec<u8> = (0..32 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let random_path = temp_dir.path().join("random.bin");
+        std::fs::write(&random_path, &random).unwrap();
+        let text_path = temp_dir.path().join("log.txt");
+        std::fs::write(&text_path, b"GET /index.html 200\n".repeat(1000)).unwrap();
+
+        let mut config = PackConfig::new(temp_dir.path(), "archive.zip");
+        config.entropy_threshold = Some(7.5);
+        let method_of = |path: &Path| {
+            let metadata = std::fs::metadata(path).unwrap();
+            let (method, _, reason) = compression_for(&config, path, &metadata);
+            (method, reason)
+        };
+        assert_eq!(
+            method_of(&random_path),
+            (CompressionMethod::Stored, CompressionReason::Entropy)
+        );
+        assert_eq!(
+            method_of(&text_path),
+            (CompressionMethod::Deflated, CompressionReason::Default)
+        );
+        assert!(sample_entropy(&random_path).unwrap() > 7.9);
+        assert!(sample_entropy(&text_path).unwrap() < 5.0);
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::provenance::compression_name;
+use crate::{ChecksumAlgorithm, FileDigest};
+
+/// Writes a CSV listing every file entry of a zip archive, for auditing in a
+/// spreadsheet:
+///
+/// ```text
+/// path,size,compressed_size,method,sha256
+/// src/main.rs,1024,412,deflated,9f86d081884c7d659a2feaa0c55ad015...
+/// ```
+///
+/// Sizes and methods are read from the central directory. The digest column
+/// is named after `algorithm` and holds the [`PackSummary::digests`] of the
+/// run (see [`PackConfig::hash_files`]), or is empty without them.
+///
+/// [`PackSummary::digests`]: crate::PackSummary::digests
+/// [`PackConfig::hash_files`]: crate::PackConfig::hash_files
+pub fn write_csv_report(
+    archive_path: &Path,
+    digests: &[FileDigest],
+    algorithm: ChecksumAlgorithm,
+    report_path: &Path,
+) -> Result<()> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open archive: {:?}", archive_path))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read archive: {:?}", archive_path))?;
+    let digests: HashMap<&str, &str> = digests
+        .iter()
+        .map(|d| (d.entry_name.as_str(), d.digest.as_str()))
+        .collect();
+
+    let report = File::create(report_path)
+        .with_context(|| format!("Failed to create report: {:?}", report_path))?;
+    let mut writer = BufWriter::new(report);
+    writeln!(writer, "path,size,compressed_size,method,{}", algorithm)?;
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            csv_field(entry.name()),
+            entry.size(),
+            entry.compressed_size(),
+            compression_name(entry.compression()),
+            digests.get(entry.name()).copied().unwrap_or_default()
+        )?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quotes a field holding a comma, quote or line break, doubling its quotes.
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_csv_report_rows() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("notes, draft.txt"), vec![b'a'; 4096]).unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        config.hash_files = Some(ChecksumAlgorithm::Sha256);
+        let summary = pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let report_path = temp_dir.path().join("report.csv");
+        write_csv_report(
+            &config.output_path,
+            &summary.digests,
+            ChecksumAlgorithm::Sha256,
+            &report_path,
+        )
+        .unwrap();
+        let report = std::fs::read_to_string(&report_path).unwrap();
+        let mut lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.remove(0), "path,size,compressed_size,method,sha256");
+        lines.sort();
+        assert_eq!(lines.len(), 2);
+
+        let fields: Vec<&str> = lines[0].rsplitn(5, ',').collect();
+        assert_eq!(fields[4], "\"notes, draft.txt\"");
+        assert_eq!(fields[3], "4096");
+        assert!(fields[2].parse::<u64>().unwrap() < 4096);
+        assert_eq!(fields[1], "deflated");
+        let digest = crate::hash_file(&root.join("notes, draft.txt"), ChecksumAlgorithm::Sha256);
+        assert_eq!(fields[0], digest.unwrap());
+
+        let fields: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(fields[..2], ["src/main.rs", "12"]);
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use flate2::Crc;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, Metadata};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::archive_diff::entry_digests;
+use crate::progress::json_string;
+
+/// Name of the entry listing the base entries a delta archive relies on, see
+/// [`PackConfig::delta_base`](crate::PackConfig::delta_base).
+pub const DELTA_NAME: &str = "SRCPACK_DELTA.json";
+
+/// The entries of a base archive, against which a delta archive is packed.
+pub(crate) struct DeltaBase {
+    path: PathBuf,
+    entries: HashMap<String, (u32, u64)>,
+    /// Entries left out of the delta because the base holds them, with their CRC.
+    reused: BTreeMap<String, u32>,
+}
+
+impl DeltaBase {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: entry_digests(path)?.into_iter().collect(),
+            reused: BTreeMap::new(),
+        })
+    }
+
+    /// Returns true, and records the entry as reused, if the base stores the
+    /// same content under this name. The file is only read when its size
+    /// matches the base entry.
+    pub(crate) fn holds(&mut self, name: &str, path: &Path, metadata: &Metadata) -> Result<bool> {
+        let Some(&(crc, size)) = self.entries.get(name) else {
+            return Ok(false);
+        };
+        if size != metadata.len() || file_crc(path)? != crc {
+            return Ok(false);
+        }
+        self.reused.insert(name.to_string(), crc);
+        Ok(true)
+    }
+
+    /// Renders the [`DELTA_NAME`] entry: the base archive's file name and the
+    /// reused entries with the CRC they have there.
+    ///
+    /// ```text
+    /// {"base":"backup-1.zip","reused":{"README.md":"3610a686","src/lib.rs":"8c736521"}}
+    /// ```
+    ///
+    /// Restoring takes every entry of the delta plus the reused entries of the
+    /// base; base entries that aren't listed were changed or removed.
+    pub(crate) fn to_json(&self) -> String {
+        let base = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let reused: Vec<String> = self
+            .reused
+            .iter()
+            .map(|(name, crc)| format!("{}:\"{:08x}\"", json_string(name), crc))
+            .collect();
+        format!(
+            r#"{{"base":{},"reused":{{{}}}}}"#,
+            json_string(&base),
+            reused.join(",")
+        )
+    }
+}
+
+/// CRC-32 of a file, as recorded in zip archives.
+fn file_crc(path: &Path) -> Result<u32> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut crc = Crc::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        if read == 0 {
+            return Ok(crc.sum());
+        }
+        crc.update(&buffer[..read]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_delta_holds_only_changed_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+        std::fs::write(root.join("CHANGELOG.md"), b"0.1.0").unwrap();
+
+        let base = temp_dir.path().join("base.zip");
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        pack_files(&files, &PackConfig::new(&root, &base), |_, _, _| {}).unwrap();
+
+        // Same size, different content: only the CRC tells them apart
+        std::fs::write(root.join("CHANGELOG.md"), b"0.1.1").unwrap();
+        std::fs::remove_file(root.join("README.md")).unwrap();
+        std::fs::write(root.join("NOTES.md"), b"new").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("delta.zip"));
+        config.delta_base = Some(base.clone());
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let mut delta = zip::ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        let mut names: Vec<&str> = delta.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["CHANGELOG.md", "NOTES.md", DELTA_NAME]);
+
+        let mut json = String::new();
+        delta
+            .by_name(DELTA_NAME)
+            .unwrap()
+            .read_to_string(&mut json)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let crc = entry_digests(&base).unwrap()["src/main.rs"].0;
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "base": "base.zip",
+                "reused": {"src/main.rs": format!("{:08x}", crc)}
+            })
+        );
+    }
+}
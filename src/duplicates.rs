@@ -0,0 +1,190 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::long_names::fit_name;
+use crate::{full_archive_entries, PackConfig};
+
+/// What to do when several files end up with the same entry name, e.g. once
+/// [`PackConfig::entry_name_normalization`] or [`PackConfig::strip_entry_prefix`]
+/// is applied, or with an attachment named like a scanned file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Packing fails before anything is written.
+    #[default]
+    Error,
+    /// Later files get a `~2`, `~3`, ... suffix before their extension.
+    Rename,
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicatePolicy::Error => "error",
+            DuplicatePolicy::Rename => "rename",
+        })
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(DuplicatePolicy::Error),
+            "rename" => Ok(DuplicatePolicy::Rename),
+            _ => anyhow::bail!(
+                "Unknown duplicate name policy {:?} (expected error or rename)",
+                s
+            ),
+        }
+    }
+}
+
+/// A name given to more than one file, see [`find_duplicate_names`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateName {
+    pub entry_name: String,
+    /// The files mapped to it, in packing order.
+    pub sources: Vec<PathBuf>,
+}
+
+/// Finds the files that would be stored under the same entry name, once every
+/// transform of the config is applied. Directories sharing a name are merged
+/// into one entry, so they are not reported.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{find_duplicate_names, scan_files, NameNorm, PackConfig, ScanConfig};
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// let mut config = PackConfig::new(".", "archive.zip");
+/// config.entry_name_normalization = NameNorm::Lowercase;
+/// for duplicate in find_duplicate_names(&files, &config) {
+///     println!("{}: {:?}", duplicate.entry_name, duplicate.sources);
+/// }
+/// ```
+pub fn find_duplicate_names(files: &[PathBuf], config: &PackConfig) -> Vec<DuplicateName> {
+    let mut duplicates: Vec<DuplicateName> = Vec::new();
+    let mut index_by_name: HashMap<String, usize> = HashMap::new();
+
+    for (path, name) in full_archive_entries(files, config) {
+        if path.is_dir() {
+            continue;
+        }
+        let name = fit_name(name, config);
+        match index_by_name.get(&name) {
+            Some(&index) => duplicates[index].sources.push(path.clone()),
+            None => {
+                index_by_name.insert(name.clone(), duplicates.len());
+                duplicates.push(DuplicateName {
+                    entry_name: name,
+                    sources: vec![path.clone()],
+                });
+            }
+        }
+    }
+
+    duplicates.retain(|duplicate| duplicate.sources.len() > 1);
+    duplicates
+}
+
+/// Hands out entry names, applying [`PackConfig::duplicate_names`] to the
+/// names already taken.
+#[derive(Default)]
+pub(crate) struct UniqueNames {
+    taken: HashSet<String>,
+}
+
+impl UniqueNames {
+    /// Returns the name to store, or `None` for a directory whose entry is
+    /// already there.
+    pub(crate) fn claim(
+        &mut self,
+        name: String,
+        is_dir: bool,
+        policy: DuplicatePolicy,
+    ) -> Option<String> {
+        if self.taken.insert(name.clone()) {
+            return Some(name);
+        }
+        if is_dir {
+            return None;
+        }
+        match policy {
+            // Refused by `pack_entries` before anything is written
+            DuplicatePolicy::Error => Some(name),
+            DuplicatePolicy::Rename => (2..)
+                .map(|n| numbered_name(&name, n))
+                .find(|candidate| self.taken.insert(candidate.clone())),
+        }
+    }
+}
+
+/// Inserts `~<n>` before the extension of the file name: `src/a~2.txt`.
+fn numbered_name(name: &str, n: usize) -> String {
+    let file_start = name.rfind('/').map_or(0, |slash| slash + 1);
+    match name[file_start..].rfind('.') {
+        // Not a dotfile
+        Some(dot) if dot > 0 => {
+            let dot = file_start + dot;
+            format!("{}~{}{}", &name[..dot], n, &name[dot..])
+        }
+        _ => format!("{}~{}", name, n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{entry_names, pack_files, scan_files, NameNorm, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_duplicate_names_per_policy() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs/Notes.txt"), b"upper").unwrap();
+        std::fs::write(root.join("docs/notes.txt"), b"lower").unwrap();
+        std::fs::write(root.join("README"), b"readme").unwrap();
+        std::fs::write(root.join("readme"), b"other").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        config.entry_name_normalization = NameNorm::Lowercase;
+
+        let mut duplicates: Vec<String> = find_duplicate_names(&files, &config)
+            .into_iter()
+            .map(|duplicate| duplicate.entry_name)
+            .collect();
+        duplicates.sort();
+        assert_eq!(duplicates, vec!["docs/notes.txt", "readme"]);
+
+        std::fs::write(&config.output_path, b"previous archive").unwrap();
+        let err = pack_files(&files, &config, |_, _, _| {}).unwrap_err();
+        assert!(
+            err.to_string().contains("2 files would be stored as"),
+            "{}",
+            err
+        );
+        // The existing archive is not replaced by an empty one
+        assert_eq!(
+            std::fs::read(&config.output_path).unwrap(),
+            b"previous archive"
+        );
+
+        config.duplicate_names = DuplicatePolicy::Rename;
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let mut names = entry_names(&files, &config);
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["docs/notes.txt", "docs/notes~2.txt", "readme", "readme~2"]
+        );
+        let archive = zip::ZipArchive::new(std::fs::File::open(&config.output_path).unwrap());
+        assert_eq!(archive.unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,575 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder, Glob};
+use ignore::Match;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::patterns::LayeredPatterns;
+use crate::presets::preset_matcher;
+use crate::{override_glob, read_keep_file, Preset, ScanConfig, KEEP_FILE_NAME};
+
+/// The rule deciding whether a path is packed, as reported by [`explain_path`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// No rule excludes the path: it is packed.
+    Included,
+    /// A user include pattern (`!pattern`) forces the path in, regardless of ignore files.
+    ForceIncluded { pattern: String },
+    /// A force-include glob ([`ScanConfig::force_include`]) adds the file.
+    ForceAdded { pattern: String },
+    /// A glob of the [`KEEP_FILE_NAME`](crate::KEEP_FILE_NAME) allowlist adds the file.
+    Kept { pattern: String },
+    /// A user exclude pattern matched the path or one of its parent directories.
+    ExcludedByPattern { pattern: String, matched: String },
+    /// Include patterns were given, and the path matches none of them.
+    NotIncluded,
+    /// A pattern of one of the [`ScanConfig::presets`] matched the path or one
+    /// of its parent directories. User include patterns don't bring it back.
+    ExcludedByPreset {
+        preset: Preset,
+        pattern: String,
+        matched: String,
+    },
+    /// A rule from an ignore file (`.gitignore`, `.ignore`, `.git/info/exclude` or
+    /// the global git excludes file) matched the path or one of its parent directories.
+    ExcludedByIgnoreFile {
+        file: PathBuf,
+        line: Option<usize>,
+        pattern: String,
+        matched: String,
+    },
+}
+
+impl Verdict {
+    /// Returns true if the path ends up in the archive.
+    pub fn is_included(&self) -> bool {
+        matches!(
+            self,
+            Verdict::Included
+                | Verdict::ForceIncluded { .. }
+                | Verdict::ForceAdded { .. }
+                | Verdict::Kept { .. }
+        )
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Included => write!(f, "included (no rule excludes it)"),
+            Verdict::ForceIncluded { pattern } => {
+                write!(f, "included (forced by include pattern `!{}`)", pattern)
+            }
+            Verdict::ForceAdded { pattern } => {
+                write!(f, "included (forced by --add `{}`)", pattern)
+            }
+            Verdict::Kept { pattern } => {
+                write!(f, "included (kept by `{}` in {})", pattern, KEEP_FILE_NAME)
+            }
+            Verdict::ExcludedByPattern { pattern, matched } => {
+                write!(
+                    f,
+                    "excluded by user pattern `{}` (matched {})",
+                    pattern, matched
+                )
+            }
+            Verdict::NotIncluded => {
+                write!(f, "excluded (include patterns were given, none matches)")
+            }
+            Verdict::ExcludedByPreset {
+                preset,
+                pattern,
+                matched,
+            } => {
+                write!(
+                    f,
+                    "excluded by `{}` of the {} preset (matched {})",
+                    pattern, preset, matched
+                )
+            }
+            Verdict::ExcludedByIgnoreFile {
+                file,
+                line,
+                pattern,
+                matched,
+            } => {
+                write!(f, "excluded by rule `{}` in {}", pattern, file.display())?;
+                if let Some(line) = line {
+                    write!(f, ":{}", line)?;
+                }
+                write!(f, " (matched {})", matched)
+            }
+        }
+    }
+}
+
+/// Explains why a path is or isn't packed by [`scan_files`](crate::scan_files).
+///
+/// The path and each of its parent directories below the root are checked in
+/// the same order as the walk: presets first, then user patterns, then ignore
+/// files from the deepest directory upwards (`.ignore`, `.gitignore`,
+/// `.git/info/exclude`, then the global git excludes file).
+///
+/// # Arguments
+///
+/// * `config` - The scan configuration (root path, user patterns and presets).
+/// * `path` - The path to explain. Relative paths are resolved against the root.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{explain_path, ScanConfig};
+/// use std::path::Path;
+///
+/// let config = ScanConfig::new(".", vec![]);
+/// let verdict = explain_path(&config, Path::new("target/debug/app")).unwrap();
+/// println!("{}", verdict); // excluded by rule `/target` in ./.gitignore:1 (matched target/)
+/// ```
+pub fn explain_path(config: &ScanConfig, path: &Path) -> Result<Verdict> {
+    let root = config
+        .root_path
+        .canonicalize()
+        .with_context(|| format!("Cannot access directory: {:?}", config.root_path))?;
+    let target = root
+        .join(path)
+        .canonicalize()
+        .with_context(|| format!("Cannot access path: {:?}", path))?;
+    let relative = target
+        .strip_prefix(&root)
+        .with_context(|| format!("{:?} is not under {:?}", path, root))?;
+    Ok(Explainer::new(config, &root)?.explain(relative, target.is_dir()))
+}
+
+/// The matchers behind [`explain_path`], built once to explain many paths.
+pub(crate) struct Explainer {
+    root: PathBuf,
+    force_added: Gitignore,
+    kept: Gitignore,
+    layered: Option<LayeredPatterns>,
+    presets: Vec<Preset>,
+    preset_matcher: Option<Gitignore>,
+    user_patterns: Gitignore,
+    ignore_files: IgnoreFiles,
+}
+
+impl Explainer {
+    /// `root` is the canonical root of the scan.
+    pub(crate) fn new(config: &ScanConfig, root: &Path) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &config.force_include {
+            builder
+                .add_line(None, pattern)
+                .with_context(|| format!("Invalid --add pattern: {:?}", pattern))?;
+        }
+        let force_added = builder.build()?;
+
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in read_keep_file(root)? {
+            builder
+                .add_line(None, &pattern)
+                .with_context(|| format!("Invalid pattern in {}: {:?}", KEEP_FILE_NAME, pattern))?;
+        }
+        let kept = builder.build()?;
+
+        let layered = LayeredPatterns::new(root, &config.exclude_patterns)?;
+        let user_patterns = match layered {
+            Some(_) => user_pattern_matcher(&[], root)?,
+            None => user_pattern_matcher(&config.exclude_patterns, root)?,
+        };
+        Ok(Self {
+            root: root.to_path_buf(),
+            force_added,
+            kept,
+            layered,
+            presets: config.presets.clone(),
+            preset_matcher: preset_matcher(root, &config.presets)?,
+            user_patterns,
+            ignore_files: IgnoreFiles {
+                requires_repo: config.gitignore_requires_repo,
+                stop_at: (!config.parent_ignores).then(|| root.to_path_buf()),
+                ..IgnoreFiles::default()
+            },
+        })
+    }
+
+    /// Explains a path given relative to the root. Only the ignore files of its
+    /// parent directories are read: the path itself may be a dangling symlink.
+    pub(crate) fn explain(&mut self, relative: &Path, is_dir: bool) -> Verdict {
+        let root = &self.root;
+        let target = root.join(relative);
+
+        // Force-included files are packed whatever the other rules say
+        if !is_dir {
+            if let Match::Ignore(glob) = self.force_added.matched(&target, false) {
+                return Verdict::ForceAdded {
+                    pattern: glob.original().to_string(),
+                };
+            }
+            if let Match::Ignore(glob) = self.kept.matched(&target, false) {
+                return Verdict::Kept {
+                    pattern: glob.original().to_string(),
+                };
+            }
+        }
+
+        // Excludes mixed with re-includes decide on their own, before ignore files
+        if let Some(decision) = self
+            .layered
+            .as_ref()
+            .and_then(|layered| layered.decide(&target, is_dir))
+            .filter(|decision| !decision.include)
+        {
+            let is_dir = decision.matched != target || is_dir;
+            return Verdict::ExcludedByPattern {
+                pattern: decision.pattern.to_string(),
+                matched: display_name(root, &decision.matched, is_dir),
+            };
+        }
+
+        let components: Vec<_> = relative.components().collect();
+        let mut level = root.clone();
+        let mut verdict = Verdict::Included;
+
+        for (i, component) in components.iter().enumerate() {
+            level.push(component);
+            let is_dir = i + 1 < components.len() || is_dir;
+            let matched = display_name(root, &level, is_dir);
+
+            // Presets prune the walk whatever the patterns and ignore files say
+            if let Some(verdict) = self.preset_verdict(&level, is_dir, &matched) {
+                return verdict;
+            }
+
+            // User patterns have the highest precedence. Their matcher is built
+            // like the override matcher, so the match kinds are inverted.
+            match self.user_patterns.matched(&level, is_dir) {
+                Match::Whitelist(glob) => {
+                    return Verdict::ExcludedByPattern {
+                        pattern: glob.original().trim_start_matches('!').to_string(),
+                        matched,
+                    }
+                }
+                Match::Ignore(glob) => {
+                    verdict = Verdict::ForceIncluded {
+                        pattern: glob.original().to_string(),
+                    };
+                    continue;
+                }
+                Match::None if self.user_patterns.num_ignores() > 0 && !is_dir => {
+                    return Verdict::NotIncluded;
+                }
+                Match::None => verdict = Verdict::Included,
+            }
+
+            if let Some(glob) = self.ignore_files.matched(&level, is_dir) {
+                let file = glob.from().map(Path::to_path_buf).unwrap_or_default();
+                return Verdict::ExcludedByIgnoreFile {
+                    line: find_line(&file, glob.original()),
+                    file,
+                    pattern: glob.original().to_string(),
+                    matched,
+                };
+            }
+        }
+
+        verdict
+    }
+
+    /// Applies the rules the walker itself checks to an entry whose parents
+    /// were walked: the user patterns, then the ignore files. Unlike
+    /// [`explain`](Self::explain), parent directories are not looked at.
+    ///
+    /// Returns `Match::Ignore` with the verdict when a rule excludes the entry,
+    /// `Match::Whitelist` when one keeps it (hidden or not), `Match::None`
+    /// otherwise.
+    pub(crate) fn walker_rules(&mut self, path: &Path, is_dir: bool) -> Match<Verdict> {
+        let matched = display_name(&self.root, path, is_dir);
+        match self.user_patterns.matched(path, is_dir) {
+            Match::Whitelist(glob) => {
+                return Match::Ignore(Verdict::ExcludedByPattern {
+                    pattern: glob.original().trim_start_matches('!').to_string(),
+                    matched,
+                })
+            }
+            Match::Ignore(glob) => {
+                return Match::Whitelist(Verdict::ForceIncluded {
+                    pattern: glob.original().to_string(),
+                })
+            }
+            Match::None if self.user_patterns.num_ignores() > 0 && !is_dir => {
+                return Match::Ignore(Verdict::NotIncluded)
+            }
+            Match::None => {}
+        }
+
+        match self.ignore_files.decide(path, is_dir) {
+            Some(Match::Ignore(glob)) => {
+                let file = glob.from().map(Path::to_path_buf).unwrap_or_default();
+                Match::Ignore(Verdict::ExcludedByIgnoreFile {
+                    line: find_line(&file, glob.original()),
+                    file,
+                    pattern: glob.original().to_string(),
+                    matched,
+                })
+            }
+            Some(_) => Match::Whitelist(Verdict::Included),
+            None => Match::None,
+        }
+    }
+
+    /// Takes the errors of the ignore files read so far (invalid globs,
+    /// unreadable files), which the walker would have reported.
+    pub(crate) fn take_errors(&mut self) -> Vec<ignore::Error> {
+        std::mem::take(&mut self.ignore_files.errors)
+    }
+
+    /// The preset excluding a path, if any.
+    fn preset_verdict(&self, path: &Path, is_dir: bool, matched: &str) -> Option<Verdict> {
+        let Match::Ignore(glob) = self.preset_matcher.as_ref()?.matched(path, is_dir) else {
+            return None;
+        };
+        let pattern = glob.original();
+        let preset = *self
+            .presets
+            .iter()
+            .find(|preset| preset.patterns().contains(&pattern))?;
+        Some(Verdict::ExcludedByPreset {
+            preset,
+            pattern: pattern.to_string(),
+            matched: matched.to_string(),
+        })
+    }
+}
+
+/// Builds a matcher over the user patterns with the same globs as the
+/// override matcher of `scan_files`, keeping track of which glob matched.
+fn user_pattern_matcher(patterns: &[String], root: &Path) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        let (glob, _) = override_glob(pattern);
+        builder
+            .add_line(None, &glob)
+            .with_context(|| format!("Invalid pattern: {:?}", pattern))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// The ignore files found in a single directory.
+struct DirIgnores {
+    ignore: Gitignore,
+    gitignore: Gitignore,
+    git_exclude: Option<Gitignore>,
+    has_git: bool,
+}
+
+/// Lazily loaded ignore files, cached per directory.
+#[derive(Default)]
+struct IgnoreFiles {
+    dirs: HashMap<PathBuf, DirIgnores>,
+    global: Option<Gitignore>,
+    /// Git ignore rules only apply inside a repository, see
+    /// [`ScanConfig::gitignore_requires_repo`].
+    requires_repo: bool,
+    /// Without [`ScanConfig::parent_ignores`], the root: the directories above
+    /// it are not looked at.
+    stop_at: Option<PathBuf>,
+    /// Errors of the ignore files that exist but couldn't be read or parsed.
+    errors: Vec<ignore::Error>,
+}
+
+impl IgnoreFiles {
+    /// The parent directories of `path` whose ignore files apply to it.
+    fn parents<'a>(stop_at: Option<&'a Path>, path: &'a Path) -> impl Iterator<Item = &'a Path> {
+        path.ancestors()
+            .skip(1)
+            .take_while(move |dir| stop_at.is_none_or(|root| dir.starts_with(root)))
+    }
+
+    /// Loads the ignore files of every parent directory of `path`.
+    fn load_parents(&mut self, path: &Path) {
+        let errors = &mut self.errors;
+        for dir in Self::parents(self.stop_at.as_deref(), path) {
+            self.dirs.entry(dir.to_path_buf()).or_insert_with(|| {
+                let has_git = dir.join(".git").exists();
+                let git_exclude = has_git.then(|| {
+                    read_ignore_file(dir.join(".git").join("info").join("exclude"), errors)
+                });
+                DirIgnores {
+                    ignore: read_ignore_file(dir.join(".ignore"), errors),
+                    gitignore: read_ignore_file(dir.join(".gitignore"), errors),
+                    git_exclude,
+                    has_git,
+                }
+            });
+        }
+        if self.global.is_none() {
+            self.global = Some(Gitignore::global().0);
+        }
+    }
+
+    /// Returns the ignore rule excluding `path`, if any, following the
+    /// precedence of the walker.
+    fn matched(&mut self, path: &Path, is_dir: bool) -> Option<Glob> {
+        match self.decide(path, is_dir) {
+            Some(Match::Ignore(glob)) => Some(glob),
+            _ => None,
+        }
+    }
+
+    /// Returns the ignore rule deciding on `path`, excluding it or keeping it
+    /// (`!pattern`), if any.
+    fn decide(&mut self, path: &Path, is_dir: bool) -> Option<Match<Glob>> {
+        self.load_parents(path);
+
+        let (mut m_ignore, mut m_gitignore, mut m_exclude) = (None, None, None);
+        let mut saw_git = false;
+
+        // Deeper directories take precedence over shallower ones, and git
+        // rules stop at the boundary of the enclosing repository.
+        for dir in Self::parents(self.stop_at.as_deref(), path) {
+            let dir_ignores = &self.dirs[dir];
+            if m_ignore.is_none() {
+                m_ignore = decided(dir_ignores.ignore.matched(path, is_dir));
+            }
+            if !saw_git && m_gitignore.is_none() {
+                m_gitignore = decided(dir_ignores.gitignore.matched(path, is_dir));
+            }
+            if !saw_git && m_exclude.is_none() {
+                if let Some(exclude) = &dir_ignores.git_exclude {
+                    m_exclude = decided(exclude.matched(path, is_dir));
+                }
+            }
+            saw_git = saw_git || dir_ignores.has_git;
+        }
+
+        let m_global = self
+            .global
+            .as_ref()
+            .and_then(|global| decided(global.matched(path, is_dir)));
+        let (m_gitignore, m_exclude, m_global) = match self.requires_repo && !saw_git {
+            true => (None, None, None),
+            false => (m_gitignore, m_exclude, m_global),
+        };
+
+        // A whitelist rule (`!pattern`) keeps the path; only ignores exclude it
+        m_ignore
+            .or(m_gitignore)
+            .or(m_exclude)
+            .or(m_global)
+            .map(|m| m.map(Glob::clone))
+    }
+}
+
+/// Reads an ignore file, keeping the error of one that exists but is invalid
+/// (a missing file is not an error).
+fn read_ignore_file(path: PathBuf, errors: &mut Vec<ignore::Error>) -> Gitignore {
+    let (gitignore, err) = Gitignore::new(&path);
+    if let Some(err) = err.filter(|_| path.is_file()) {
+        errors.push(err);
+    }
+    gitignore
+}
+
+/// Keeps a match only if a rule actually decided something.
+fn decided(m: Match<&Glob>) -> Option<Match<&Glob>> {
+    (!m.is_none()).then_some(m)
+}
+
+/// Formats a path relative to the root, with a trailing `/` for directories.
+fn display_name(root: &Path, path: &Path, is_dir: bool) -> String {
+    let name = crate::relative_entry_name(path, root);
+    if is_dir {
+        format!("{}/", name)
+    } else {
+        name
+    }
+}
+
+/// Finds the (last) line of an ignore file holding the given rule.
+fn find_line(file: &Path, original: &str) -> Option<usize> {
+    let content = std::fs::read_to_string(file).ok()?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.trim() == original.trim())
+        .map(|(index, _)| index + 1)
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_explain_gitignore_rule() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+
+        write(root.join(".gitignore"), "# build output\n*.log\n").unwrap();
+        write(root.join("error.log"), "error content").unwrap();
+        create_dir_all(root.join("src")).unwrap();
+        write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        write(root.join("notes.txt"), "notes").unwrap();
+
+        let config = ScanConfig::new(&root, vec!["*.txt".to_string()]);
+
+        // Excluded by the gitignore pattern, with its file and line
+        let verdict = explain_path(&config, Path::new("error.log")).unwrap();
+        assert_eq!(
+            verdict,
+            Verdict::ExcludedByIgnoreFile {
+                file: root.join(".gitignore"),
+                line: Some(2),
+                pattern: "*.log".to_string(),
+                matched: "error.log".to_string(),
+            }
+        );
+
+        // Excluded by a user pattern
+        let verdict = explain_path(&config, Path::new("notes.txt")).unwrap();
+        assert_eq!(
+            verdict,
+            Verdict::ExcludedByPattern {
+                pattern: "*.txt".to_string(),
+                matched: "notes.txt".to_string(),
+            }
+        );
+
+        // Not excluded by anything
+        let verdict = explain_path(&config, Path::new("src/main.rs")).unwrap();
+        assert_eq!(verdict, Verdict::Included);
+    }
+
+    #[test]
+    fn test_explain_preset() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        create_dir_all(root.join("pkg/__pycache__")).unwrap();
+        write(root.join("pkg/__pycache__/mod.cpython-312.pyc"), "").unwrap();
+        write(root.join("pkg/mod.py"), "").unwrap();
+
+        // An include pattern doesn't bring back what a preset prunes
+        let mut config = ScanConfig::new(&root, vec!["!*.pyc".to_string()]);
+        config.presets = vec![Preset::Macos, Preset::Python];
+        let verdict = explain_path(&config, Path::new("pkg/__pycache__/mod.cpython-312.pyc"));
+        assert_eq!(
+            verdict.unwrap(),
+            Verdict::ExcludedByPreset {
+                preset: Preset::Python,
+                pattern: "__pycache__/".to_string(),
+                matched: "pkg/__pycache__/".to_string(),
+            }
+        );
+        assert!(crate::scan_files(&config)
+            .unwrap()
+            .iter()
+            .all(|path| !path.to_string_lossy().contains("__pycache__")));
+
+        // Outside of the presets, the include pattern decides
+        let verdict = explain_path(&config, Path::new("pkg/mod.py")).unwrap();
+        assert_eq!(verdict, Verdict::NotIncluded);
+    }
+}
@@ -0,0 +1,794 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use zip::read::ZipFile;
+use zip::{DateTime, ZipArchive};
+
+use crate::mtimes::{read_mtimes, set_mtime, MTIMES_NAME};
+use crate::ownership::restore_owner;
+
+/// What to do with entries whose destination already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+    /// Keep the existing file.
+    Skip,
+    /// Replace the existing file only if the entry's modification time is
+    /// later. Zip times have a two-second resolution and are read as UTC.
+    Newer,
+}
+
+impl fmt::Display for OverwritePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverwritePolicy::Overwrite => "overwrite",
+            OverwritePolicy::Skip => "skip",
+            OverwritePolicy::Newer => "newer",
+        })
+    }
+}
+
+impl FromStr for OverwritePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "overwrite" => Ok(OverwritePolicy::Overwrite),
+            "skip" => Ok(OverwritePolicy::Skip),
+            "newer" => Ok(OverwritePolicy::Newer),
+            _ => anyhow::bail!(
+                "Unknown overwrite policy {:?} (expected skip, overwrite or newer)",
+                s
+            ),
+        }
+    }
+}
+
+/// Default of [`ExtractConfig::max_total_size`]: 8 GiB.
+pub const DEFAULT_MAX_EXTRACT_SIZE: u64 = 8 * 1024 * 1024 * 1024;
+
+/// Default of [`ExtractConfig::max_entries`].
+pub const DEFAULT_MAX_EXTRACT_ENTRIES: usize = 1_000_000;
+
+/// Default of [`ExtractConfig::max_ratio`]. Deflate tops out around 1032:1,
+/// so only a crafted (or zstd-compressed, highly repetitive) entry exceeds it.
+pub const DEFAULT_MAX_RATIO: u64 = 2000;
+
+/// Entries smaller than this are not held to [`ExtractConfig::max_ratio`].
+const RATIO_MIN_SIZE: u64 = 1024 * 1024;
+
+/// Configuration for extracting an archive.
+pub struct ExtractConfig {
+    /// The zip archive to extract.
+    pub archive_path: PathBuf,
+    /// The directory the entries are written into.
+    pub dest_dir: PathBuf,
+    /// Leading path components removed from each entry name, like
+    /// `tar --strip-components`. Entries with no components left are skipped.
+    pub strip_components: usize,
+    /// Give entries the uid/gid recorded with [`PackConfig::preserve_ownership`]
+    /// (unix only). Without the privilege to do so, a warning is printed.
+    ///
+    /// [`PackConfig::preserve_ownership`]: crate::PackConfig::preserve_ownership
+    pub preserve_ownership: bool,
+    /// What to do when an entry's destination already exists.
+    pub on_conflict: OverwritePolicy,
+    /// Refuse archives whose entries add up to more than this many bytes
+    /// once extracted. `None` for no limit.
+    pub max_total_size: Option<u64>,
+    /// Refuse archives with more entries than this. `None` for no limit.
+    pub max_entries: Option<usize>,
+    /// Refuse archives with an entry over 1 MiB whose uncompressed size is
+    /// more than this many times its compressed size. `None` for no limit.
+    pub max_ratio: Option<u64>,
+    /// Give files the nanosecond modification times recorded with
+    /// [`PackConfig::embed_mtimes`]. Without them, a warning is printed.
+    ///
+    /// [`PackConfig::embed_mtimes`]: crate::PackConfig::embed_mtimes
+    pub restore_mtimes: bool,
+}
+
+impl ExtractConfig {
+    /// Creates a new `ExtractConfig` extracting `archive` into `dest`.
+    pub fn new(archive: impl Into<PathBuf>, dest: impl Into<PathBuf>) -> Self {
+        Self {
+            archive_path: archive.into(),
+            dest_dir: dest.into(),
+            strip_components: 0,
+            preserve_ownership: false,
+            on_conflict: OverwritePolicy::Overwrite,
+            max_total_size: Some(DEFAULT_MAX_EXTRACT_SIZE),
+            max_entries: Some(DEFAULT_MAX_EXTRACT_ENTRIES),
+            max_ratio: Some(DEFAULT_MAX_RATIO),
+            restore_mtimes: false,
+        }
+    }
+}
+
+/// What extracting an entry would do, see [`plan_extract`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryAction {
+    /// Written to a path that doesn't exist yet.
+    Create,
+    /// Written over an existing file or directory.
+    Overwrite,
+    /// Left out, as the destination exists (see [`ExtractConfig::on_conflict`]).
+    Skip,
+    /// Refused: the name is absolute or escapes the destination with `..`,
+    /// or the entry is a symlink out of it or would be written through one.
+    Reject,
+}
+
+impl fmt::Display for EntryAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            EntryAction::Create => "create",
+            EntryAction::Overwrite => "overwrite",
+            EntryAction::Skip => "skip",
+            EntryAction::Reject => "reject",
+        })
+    }
+}
+
+/// An archive entry and where [`extract_archive`] would write it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedEntry {
+    /// The entry name, as stored in the archive.
+    pub name: String,
+    /// The resolved destination, `None` for rejected entries.
+    pub dest: Option<PathBuf>,
+    pub action: EntryAction,
+}
+
+/// Resolves every entry of the archive to its destination, without writing
+/// anything, for a dry run of [`extract_archive`].
+///
+/// Entries left without components by [`ExtractConfig::strip_components`]
+/// are not listed. Where [`extract_archive`] stops at the first unsafe entry,
+/// this lists all of them as [`EntryAction::Reject`], including those below a
+/// symlink entry listed before them.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{plan_extract, EntryAction, ExtractConfig};
+///
+/// let plan = plan_extract(&ExtractConfig::new("download.zip", "out")).unwrap();
+/// for entry in plan.iter().filter(|e| e.action == EntryAction::Reject) {
+///     println!("Unsafe entry: {}", entry.name);
+/// }
+/// ```
+pub fn plan_extract(config: &ExtractConfig) -> Result<Vec<PlannedEntry>> {
+    let mut archive = open_archive(&config.archive_path)?;
+
+    let mut plan = Vec::new();
+    // Symlink entries, which the entries after them would be written through
+    let mut planned_links = HashSet::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+        let relative = entry
+            .enclosed_name()
+            .map(|enclosed| strip_components(&enclosed, config.strip_components));
+        let relative = match relative {
+            Some(Some(relative)) => relative,
+            Some(None) => continue,
+            None => {
+                plan.push(PlannedEntry {
+                    name,
+                    dest: None,
+                    action: EntryAction::Reject,
+                });
+                continue;
+            }
+        };
+
+        let mut safe = symlinked_parent(&config.dest_dir, &relative, &planned_links).is_none();
+        if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            safe &= !link_escapes(&relative, &target);
+            planned_links.insert(relative.clone());
+        }
+        let dest = config.dest_dir.join(relative);
+        plan.push(match safe {
+            true => PlannedEntry {
+                name,
+                action: resolve_conflict(&entry, &dest, config.on_conflict),
+                dest: Some(dest),
+            },
+            false => PlannedEntry {
+                name,
+                dest: None,
+                action: EntryAction::Reject,
+            },
+        });
+    }
+    Ok(plan)
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<BufReader<File>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open archive: {:?}", path))?;
+    ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read archive: {:?}", path))
+}
+
+/// Checks the sizes the archive declares against the limits of the config,
+/// before anything is written (zip bomb guard).
+fn check_limits<R: std::io::Read + std::io::Seek>(
+    archive: &mut ZipArchive<R>,
+    config: &ExtractConfig,
+) -> Result<()> {
+    if let Some(max_entries) = config.max_entries.filter(|&max| archive.len() > max) {
+        anyhow::bail!(
+            "Archive has {} entries, more than the limit of {}",
+            archive.len(),
+            max_entries
+        );
+    }
+
+    let mut total: u64 = 0;
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index)?;
+        let size = entry.size();
+        if let Some(max_ratio) = config.max_ratio {
+            if size > RATIO_MIN_SIZE && size / entry.compressed_size().max(1) > max_ratio {
+                anyhow::bail!(
+                    "Entry {:?} expands {} bytes to {}, a compression ratio over the limit of {}:1",
+                    entry.name(),
+                    entry.compressed_size(),
+                    size,
+                    max_ratio
+                );
+            }
+        }
+        total = total.saturating_add(size);
+    }
+    if let Some(max_size) = config.max_total_size.filter(|&max| total > max) {
+        anyhow::bail!(
+            "Archive expands to {} bytes, more than the limit of {}",
+            total,
+            max_size
+        );
+    }
+    Ok(())
+}
+
+/// Applies the overwrite policy to an entry about to be written to `dest`.
+fn resolve_conflict<R: std::io::Read>(
+    entry: &ZipFile<'_, R>,
+    dest: &Path,
+    policy: OverwritePolicy,
+) -> EntryAction {
+    let Ok(metadata) = dest.symlink_metadata() else {
+        return EntryAction::Create;
+    };
+    let replace = match policy {
+        OverwritePolicy::Overwrite => true,
+        OverwritePolicy::Skip => false,
+        // Without both times there is nothing to say the entry is newer
+        OverwritePolicy::Newer => {
+            match (
+                entry.last_modified().and_then(system_time),
+                metadata.modified(),
+            ) {
+                (Some(entry_time), Ok(disk_time)) => entry_time > disk_time,
+                _ => false,
+            }
+        }
+    };
+    match replace {
+        true => EntryAction::Overwrite,
+        false => EntryAction::Skip,
+    }
+}
+
+/// Converts a zip timestamp, read as UTC, to a `SystemTime`.
+fn system_time(time: DateTime) -> Option<SystemTime> {
+    let days = days_from_civil(
+        i64::from(time.year()),
+        i64::from(time.month()),
+        i64::from(time.day()),
+    );
+    let seconds = days * 86_400
+        + i64::from(time.hour()) * 3600
+        + i64::from(time.minute()) * 60
+        + i64::from(time.second());
+    let seconds = u64::try_from(seconds).ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+/// Days between the Unix epoch and a civil date, Howard Hinnant's algorithm.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Removes the leading components of an entry path, `None` if nothing is left.
+fn strip_components(name: &Path, count: usize) -> Option<PathBuf> {
+    let relative: PathBuf = name.components().skip(count).collect();
+    (!relative.as_os_str().is_empty()).then_some(relative)
+}
+
+/// Extracts a zip archive (or self-extracting script) into a directory.
+///
+/// Entry names that would escape the destination (absolute paths, `..`) are
+/// refused ([`plan_extract`] lists them beforehand), as are symlinks pointing
+/// out of it and entries that would be written through a symlink. A symlink
+/// in place of a file is replaced, not written through. Existing files are
+/// handled by [`ExtractConfig::on_conflict`]; skipped entries are not in the
+/// returned list. Unix permissions are restored where the archive records them.
+///
+/// Archives over [`ExtractConfig::max_total_size`], [`ExtractConfig::max_entries`]
+/// or [`ExtractConfig::max_ratio`] are refused before anything is written.
+///
+/// Returns the paths written, in archive order.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{extract_archive, ExtractConfig};
+///
+/// let mut config = ExtractConfig::new("project.zip", "out");
+/// config.strip_components = 1;
+/// extract_archive(&config).unwrap();
+/// ```
+pub fn extract_archive(config: &ExtractConfig) -> Result<Vec<PathBuf>> {
+    let mut archive = open_archive(&config.archive_path)?;
+    check_limits(&mut archive, config)?;
+    let mtimes = match config.restore_mtimes {
+        true => read_mtimes(&mut archive)?,
+        false => None,
+    };
+    if config.restore_mtimes && mtimes.is_none() {
+        eprintln!(
+            "Extract warning: the archive has no {}, modification times are not restored",
+            MTIMES_NAME
+        );
+    }
+
+    let mut written = Vec::new();
+    let mut ownership_failures = 0;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry
+            .enclosed_name()
+            .with_context(|| format!("Unsafe entry name in archive: {:?}", entry.name()))?;
+
+        let Some(relative) = strip_components(&name, config.strip_components) else {
+            continue;
+        };
+        if let Some(link) = symlinked_parent(&config.dest_dir, &relative, &HashSet::new()) {
+            anyhow::bail!(
+                "Entry {:?} would be written through the symlink {:?}",
+                entry.name(),
+                link
+            );
+        }
+        let out_path = config.dest_dir.join(&relative);
+        if resolve_conflict(&entry, &out_path, config.on_conflict) == EntryAction::Skip {
+            continue;
+        }
+        // Replace a symlink in the way rather than writing to its target
+        if !entry.is_symlink() && is_symlink(&out_path) {
+            std::fs::remove_file(&out_path)
+                .with_context(|| format!("Failed to remove symlink: {:?}", out_path))?;
+        }
+
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)
+                .with_context(|| format!("Failed to create directory: {:?}", out_path))?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+            }
+            if !(entry.is_symlink() && write_symlink(&mut entry, &out_path, &relative)?) {
+                let mut out = File::create(&out_path)
+                    .with_context(|| format!("Failed to create file: {:?}", out_path))?;
+                // The limits were checked against the declared size, hold the data to it
+                let declared = entry.size();
+                let copied = std::io::copy(&mut (&mut entry).take(declared + 1), &mut out)
+                    .with_context(|| format!("Failed to extract {:?}", entry.name()))?;
+                if copied > declared {
+                    anyhow::bail!(
+                        "Entry {:?} holds more data than its declared {} bytes",
+                        entry.name(),
+                        declared
+                    );
+                }
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode().filter(|_| !entry.is_symlink()) {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        if let Some(&mtime) = mtimes
+            .as_ref()
+            .filter(|_| entry.is_file() && !entry.is_symlink())
+            .and_then(|mtimes| mtimes.get(entry.name()))
+        {
+            set_mtime(&out_path, mtime)?;
+        }
+        if config.preserve_ownership && restore_owner(&out_path, entry.extra_data()).is_err() {
+            ownership_failures += 1;
+        }
+        written.push(out_path);
+    }
+
+    if ownership_failures > 0 {
+        eprintln!(
+            "Extract warning: could not restore the owner of {} entries (root privileges are needed)",
+            ownership_failures
+        );
+    }
+
+    Ok(written)
+}
+
+/// Recreates a symlink entry, whose data is the link target. Targets that
+/// are absolute or lead out of the destination are refused.
+///
+/// Returns false where symlinks aren't supported, so the target is written
+/// as a regular file instead.
+fn write_symlink(entry: &mut impl std::io::Read, out_path: &Path, relative: &Path) -> Result<bool> {
+    #[cfg(unix)]
+    {
+        let mut target = String::new();
+        entry.read_to_string(&mut target)?;
+        if link_escapes(relative, &target) {
+            anyhow::bail!(
+                "Symlink {:?} points out of the destination, to {:?}",
+                relative,
+                target
+            );
+        }
+        if out_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(out_path)?;
+        }
+        std::os::unix::fs::symlink(&target, out_path)
+            .with_context(|| format!("Failed to create symlink: {:?}", out_path))?;
+        Ok(true)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (entry, out_path, relative);
+        Ok(false)
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    path.symlink_metadata()
+        .is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
+
+/// Returns the first symlink between the destination and the entry at
+/// `relative`, on disk or among the `planned_links` of a dry run: writing
+/// the entry would follow it, possibly out of the destination.
+fn symlinked_parent(
+    dest_dir: &Path,
+    relative: &Path,
+    planned_links: &HashSet<PathBuf>,
+) -> Option<PathBuf> {
+    let mut prefix = PathBuf::new();
+    for component in relative.parent()?.components() {
+        prefix.push(component);
+        let path = dest_dir.join(&prefix);
+        if planned_links.contains(&prefix) || is_symlink(&path) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Whether a symlink stored at `relative` below the destination, pointing to
+/// `target`, resolves outside of the destination.
+fn link_escapes(relative: &Path, target: &str) -> bool {
+    let mut depth = relative.components().count() as isize - 1;
+    for component in Path::new(target).components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir => {
+                depth -= 1;
+                if depth < 0 {
+                    return true;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_extract_strip_components() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let archive_path = temp_dir.path().join("project.zip");
+        let mut pack_config = PackConfig::new(&root, &archive_path);
+        pack_config.prefix = Some("project".to_string());
+        pack_files(&files, &pack_config, |_, _, _| {}).unwrap();
+
+        // Without stripping, everything lands under project/
+        let dest = temp_dir.path().join("full");
+        extract_archive(&ExtractConfig::new(&archive_path, &dest)).unwrap();
+        assert!(dest.join("project/src/main.rs").exists());
+
+        let dest = temp_dir.path().join("stripped");
+        let mut config = ExtractConfig::new(&archive_path, &dest);
+        config.strip_components = 1;
+        let mut written = extract_archive(&config).unwrap();
+        written.sort();
+        assert_eq!(
+            written,
+            vec![dest.join("README.md"), dest.join("src/main.rs")]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert!(!dest.join("project").exists());
+
+        // Entries with too few components are skipped
+        let dest = temp_dir.path().join("deep");
+        let mut config = ExtractConfig::new(&archive_path, &dest);
+        config.strip_components = 2;
+        let written = extract_archive(&config).unwrap();
+        assert_eq!(written, vec![dest.join("main.rs")]);
+    }
+
+    #[test]
+    fn test_plan_extract_flags_traversal() {
+        let temp_dir = tempdir().unwrap();
+        let archive_path = temp_dir.path().join("safe.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("src/main.rs", options).unwrap();
+        zip.start_file("README.md", options).unwrap();
+        zip.finish().unwrap();
+
+        let dest = temp_dir.path().join("out");
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(dest.join("README.md"), b"# Local").unwrap();
+        let plan = plan_extract(&ExtractConfig::new(&archive_path, &dest)).unwrap();
+        let actions: Vec<(&str, EntryAction)> =
+            plan.iter().map(|e| (e.name.as_str(), e.action)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("src/main.rs", EntryAction::Create),
+                ("README.md", EntryAction::Overwrite)
+            ]
+        );
+        assert_eq!(plan[0].dest, Some(dest.join("src/main.rs")));
+        // Nothing was written
+        assert!(!dest.join("src").exists());
+
+        let archive_path = temp_dir.path().join("evil.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        zip.start_file("../evil", options).unwrap();
+        zip.start_file("ok.txt", options).unwrap();
+        zip.finish().unwrap();
+        let plan = plan_extract(&ExtractConfig::new(&archive_path, &dest)).unwrap();
+        assert_eq!(
+            plan[0],
+            PlannedEntry {
+                name: "../evil".to_string(),
+                dest: None,
+                action: EntryAction::Reject,
+            }
+        );
+        assert_eq!(plan[1].action, EntryAction::Create);
+        assert!(extract_archive(&ExtractConfig::new(&archive_path, &dest)).is_err());
+        assert!(!temp_dir.path().join("evil").exists());
+    }
+
+    /// Writes a zip of `(name, data)` entries, those with a `->` prefix as
+    /// symlinks to the rest of the data.
+    #[cfg(unix)]
+    fn write_links_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in entries {
+            match data.strip_prefix("->") {
+                Some(target) => zip.add_symlink(*name, target, options).unwrap(),
+                None => {
+                    zip.start_file(*name, options).unwrap();
+                    std::io::Write::write_all(&mut zip, data.as_bytes()).unwrap();
+                }
+            }
+        }
+        zip.finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plan_extract_flags_symlink_escapes() {
+        let temp_dir = tempdir().unwrap();
+        let archive_path = temp_dir.path().join("evil.zip");
+        write_links_zip(
+            &archive_path,
+            &[
+                ("link", "->/etc"),
+                ("link/x", "pwned"),
+                ("docs/readme", "->../README.md"),
+                ("README.md", "# Hello"),
+            ],
+        );
+
+        let dest = temp_dir.path().join("out");
+        let plan = plan_extract(&ExtractConfig::new(&archive_path, &dest)).unwrap();
+        let actions: Vec<(&str, EntryAction)> =
+            plan.iter().map(|e| (e.name.as_str(), e.action)).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("link", EntryAction::Reject),
+                ("link/x", EntryAction::Reject),
+                ("docs/readme", EntryAction::Create),
+                ("README.md", EntryAction::Create)
+            ]
+        );
+        assert!(!dest.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_cannot_escape_destination() {
+        let temp_dir = tempdir().unwrap();
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("victim"), b"secret").unwrap();
+        let dest = temp_dir.path().join("out");
+        let archive_path = temp_dir.path().join("evil.zip");
+        let extract = || extract_archive(&ExtractConfig::new(&archive_path, &dest));
+
+        // A link out of the destination, then an entry below it
+        let target = format!("->{}", outside.display());
+        write_links_zip(&archive_path, &[("link", &target), ("link/x", "pwned")]);
+        assert!(extract().is_err());
+        write_links_zip(&archive_path, &[("a/link", "->../../outside")]);
+        assert!(extract().is_err());
+
+        // A link already on disk is not written through
+        std::os::unix::fs::symlink(&outside, dest.join("link")).unwrap();
+        write_links_zip(&archive_path, &[("link/x", "pwned")]);
+        let err = extract().unwrap_err();
+        assert!(err.to_string().contains("through the symlink"), "{}", err);
+        assert!(!outside.join("x").exists());
+
+        // A link in place of a file is replaced, its target is left alone
+        std::os::unix::fs::symlink(outside.join("victim"), dest.join("config")).unwrap();
+        write_links_zip(&archive_path, &[("config", "from archive")]);
+        extract().unwrap();
+        assert_eq!(std::fs::read(outside.join("victim")).unwrap(), b"secret");
+        assert!(!is_symlink(&dest.join("config")));
+        assert_eq!(std::fs::read(dest.join("config")).unwrap(), b"from archive");
+
+        // Links staying inside are recreated
+        write_links_zip(
+            &archive_path,
+            &[("b/file", "data"), ("a/ok", "->../b/file")],
+        );
+        extract().unwrap();
+        assert_eq!(std::fs::read(dest.join("a/ok")).unwrap(), b"data");
+        assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_overwrite_policies() {
+        let temp_dir = tempdir().unwrap();
+        let archive_path = temp_dir.path().join("release.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        let time = DateTime::from_date_and_time(2020, 1, 1, 0, 0, 0).unwrap();
+        let options = zip::write::SimpleFileOptions::default().last_modified_time(time);
+        zip.start_file("config.toml", options).unwrap();
+        std::io::Write::write_all(&mut zip, b"from archive").unwrap();
+        zip.finish().unwrap();
+        let entry_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_577_836_800);
+        assert_eq!(system_time(time), Some(entry_time));
+
+        let dest = temp_dir.path().join("out");
+        let path = dest.join("config.toml");
+        let extract = |policy, local_mtime: SystemTime| {
+            std::fs::create_dir_all(&dest).unwrap();
+            std::fs::write(&path, b"local").unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(local_mtime)
+                .unwrap();
+            let mut config = ExtractConfig::new(&archive_path, &dest);
+            config.on_conflict = policy;
+            let written = extract_archive(&config).unwrap();
+            (written.len(), std::fs::read_to_string(&path).unwrap())
+        };
+        let older = entry_time - Duration::from_secs(86_400);
+        let newer = entry_time + Duration::from_secs(86_400);
+
+        assert_eq!(
+            extract(OverwritePolicy::Overwrite, newer),
+            (1, "from archive".to_string())
+        );
+        assert_eq!(
+            extract(OverwritePolicy::Skip, older),
+            (0, "local".to_string())
+        );
+        assert_eq!(
+            extract(OverwritePolicy::Newer, older),
+            (1, "from archive".to_string())
+        );
+        assert_eq!(
+            extract(OverwritePolicy::Newer, newer),
+            (0, "local".to_string())
+        );
+        // The kept file's timestamp is untouched
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), newer);
+    }
+
+    #[test]
+    fn test_extract_limits_refuse_bombs() {
+        let temp_dir = tempdir().unwrap();
+        let archive_path = temp_dir.path().join("bomb.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("README.md", options).unwrap();
+        std::io::Write::write_all(&mut zip, b"# Hello").unwrap();
+        zip.start_file("bomb.bin", options).unwrap();
+        std::io::Write::write_all(&mut zip, b"0123456789").unwrap();
+        zip.finish().unwrap();
+
+        // Make the central directory declare ~3.75 GiB for bomb.bin
+        let mut bytes = std::fs::read(&archive_path).unwrap();
+        let header = bytes
+            .windows(4)
+            .rposition(|w| w == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        bytes[header + 24..header + 28].copy_from_slice(&0xF000_0000u32.to_le_bytes());
+        std::fs::write(&archive_path, bytes).unwrap();
+
+        let dest = temp_dir.path().join("out");
+        let err = extract_archive(&ExtractConfig::new(&archive_path, &dest)).unwrap_err();
+        assert!(err.to_string().contains("bomb.bin"), "{}", err);
+        assert!(!dest.exists());
+
+        let mut config = ExtractConfig::new(&archive_path, &dest);
+        config.max_ratio = None;
+        config.max_total_size = Some(1024 * 1024 * 1024);
+        let err = extract_archive(&config).unwrap_err();
+        assert!(err.to_string().contains("more than the limit"), "{}", err);
+
+        config.max_total_size = None;
+        config.max_entries = Some(1);
+        let err = extract_archive(&config).unwrap_err();
+        assert!(err.to_string().contains("2 entries"), "{}", err);
+        assert!(!dest.exists());
+    }
+}
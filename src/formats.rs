@@ -0,0 +1,437 @@
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use zip::write::FullFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::manifest::HashingReader;
+use crate::throttle::{Throttled, TokenBucket};
+use crate::{
+    check_entry_names, create_zip_writer, finish_zip_writer, pack_entries, pack_files,
+    ChecksumAlgorithm, PackConfig, PackSummary,
+};
+
+/// A gzip-compressed tar archive being written to disk.
+pub(crate) type TarWriter = tar::Builder<GzEncoder<BufWriter<File>>>;
+
+/// Archive formats srcpack can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// The file extension of the format, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "zip" => Ok(ArchiveFormat::Zip),
+            "tar.gz" | "tgz" => Ok(ArchiveFormat::TarGz),
+            _ => anyhow::bail!("Unknown archive format {:?} (expected zip or tar.gz)", s),
+        }
+    }
+}
+
+/// Where the default output name comes from when no output path is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputNaming {
+    /// The name of the root directory: packing `~/src/app` gives `app.zip`.
+    #[default]
+    Dir,
+    /// Always [`FIXED_OUTPUT_STEM`], whatever the root is called.
+    Fixed,
+}
+
+impl fmt::Display for OutputNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputNaming::Dir => "dir",
+            OutputNaming::Fixed => "fixed",
+        })
+    }
+}
+
+impl FromStr for OutputNaming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "dir" => Ok(OutputNaming::Dir),
+            "fixed" => Ok(OutputNaming::Fixed),
+            _ => anyhow::bail!("Unknown output naming {:?} (expected dir or fixed)", s),
+        }
+    }
+}
+
+/// Default output name, without extension, of [`OutputNaming::Fixed`] and of
+/// roots that have no name of their own (`/`, `C:\`).
+pub const FIXED_OUTPUT_STEM: &str = "archive";
+
+/// Returns the default output name, without extension, for packing `root`.
+///
+/// The root should be canonicalized first: `.` has no name, so it would get
+/// [`FIXED_OUTPUT_STEM`] rather than the name of the current directory.
+pub fn default_output_stem(root: &Path, naming: OutputNaming) -> String {
+    match (naming, root.file_name()) {
+        (OutputNaming::Dir, Some(name)) => name.to_string_lossy().into_owned(),
+        _ => FIXED_OUTPUT_STEM.to_string(),
+    }
+}
+
+/// Creates an empty, uniquely named file in the system temp directory to
+/// pack into, e.g. `/tmp/my-app-Xq3r9a.zip`, and returns its absolute path.
+///
+/// The file is created readable by the current user only (mode `0600` on
+/// unix), and packing into it keeps that mode. It is left in place: it is up
+/// to the caller, typically the next step of a script, to remove it.
+pub fn temp_output_path(stem: &str, extension: &str) -> Result<PathBuf> {
+    let dir = std::path::absolute(std::env::temp_dir())?;
+    let (_, path) = tempfile::Builder::new()
+        .prefix(&format!("{}-", stem))
+        .suffix(&format!(".{}", extension))
+        .tempfile_in(&dir)
+        .with_context(|| format!("Failed to create a temporary file in {:?}", dir))?
+        .keep()?;
+    Ok(path)
+}
+
+/// Returns `path` with its archive extension replaced by the one of `format`.
+///
+/// `backup.zip` becomes `backup.tar.gz`; a name without a known archive
+/// extension simply gets the new extension appended.
+pub fn output_path_for_format(path: &Path, format: ArchiveFormat) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("archive"));
+
+    let stem = [".tar.gz", ".tgz", ".zip", ".sh"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(&name);
+
+    path.with_file_name(format!("{}.{}", stem, format.extension()))
+}
+
+/// Packs the same files into several archive formats in a single pass.
+///
+/// The scan is shared and each file is read only once: its bytes are teed into
+/// every requested writer. With several formats, the output path of each one is
+/// derived from `config.output_path` with [`output_path_for_format`]; a single
+/// format is written to `config.output_path` as-is.
+///
+/// The tar.gz output uses gzip at `config.compression_level` (level 0 for
+/// `Stored`); zip-only options such as `sfx` apply to the zip output only.
+///
+/// # Returns
+///
+/// * `Result<PackSummary>` - The paths of the written archives (in the order of
+///   `formats`) and statistics about the run.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files_multi, scan_files, ArchiveFormat, PackConfig, ScanConfig};
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// let config = PackConfig::new(".", "release.zip");
+/// let summary = pack_files_multi(
+///     &files,
+///     &config,
+///     &[ArchiveFormat::Zip, ArchiveFormat::TarGz],
+///     |_, _, _| {},
+/// )
+/// .unwrap();
+/// println!("Wrote {:?}", summary.outputs); // release.zip, release.tar.gz
+/// ```
+pub fn pack_files_multi<F>(
+    files: &[PathBuf],
+    config: &PackConfig,
+    formats: &[ArchiveFormat],
+    on_progress: F,
+) -> Result<PackSummary>
+where
+    F: FnMut(&PathBuf, u64, u64),
+{
+    let output_for = |format: ArchiveFormat| match formats.len() {
+        1 => config.output_path.clone(),
+        _ => output_path_for_format(&config.output_path, format),
+    };
+    let outputs: Vec<PathBuf> = formats.iter().map(|&format| output_for(format)).collect();
+
+    let mut zip_config = config.clone();
+    zip_config.output_path = output_for(ArchiveFormat::Zip);
+
+    if formats == [ArchiveFormat::Zip] {
+        return pack_files(files, &zip_config, on_progress);
+    }
+
+    check_entry_names(files, &zip_config)?;
+    let mut zip = match formats.contains(&ArchiveFormat::Zip) {
+        true => Some(create_zip_writer(&zip_config)?),
+        false => None,
+    };
+
+    let mut tar = match formats.contains(&ArchiveFormat::TarGz) {
+        true => Some(create_tar_writer(
+            config,
+            &output_for(ArchiveFormat::TarGz),
+        )?),
+        false => None,
+    };
+
+    // The zip output is the one a stat index refers to
+    let mut summary = pack_entries(files, &zip_config, zip.as_mut(), tar.as_mut(), on_progress)?;
+
+    if let Some(zip) = zip {
+        finish_zip_writer(zip, &zip_config)?;
+    }
+    if let Some(tar) = tar {
+        tar.into_inner()?.finish()?.flush()?;
+    }
+
+    summary.outputs = outputs;
+    Ok(summary)
+}
+
+/// Creates the tar.gz output file, compressed according to the pack settings.
+fn create_tar_writer(config: &PackConfig, path: &Path) -> Result<TarWriter> {
+    let file =
+        File::create(path).with_context(|| format!("Failed to create output file: {:?}", path))?;
+
+    let level = match config.compression_method {
+        CompressionMethod::Stored => 0,
+        _ => config.compression_level.unwrap_or(6).clamp(0, 9) as u32,
+    };
+
+    let encoder = GzEncoder::new(
+        BufWriter::with_capacity(1024 * 1024, file),
+        Compression::new(level),
+    );
+    Ok(tar::Builder::new(encoder))
+}
+
+/// Builds a tar header carrying the file's size, owner and mtime, and `mode`.
+pub(crate) fn tar_header(metadata: &Metadata, mode: u32) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_metadata(metadata);
+    header.set_mode(mode & 0o7777);
+    header
+}
+
+/// Adds a directory entry to the tar archive.
+pub(crate) fn append_tar_directory(
+    tar: &mut TarWriter,
+    metadata: &Metadata,
+    entry_name: &str,
+    mode: u32,
+) -> Result<()> {
+    let mut header = tar_header(metadata, mode);
+    tar.append_data(&mut header, entry_name, io::empty())?;
+    Ok(())
+}
+
+/// Adds a file entry holding `data`, generated rather than read from disk.
+pub(crate) fn append_tar_bytes(tar: &mut TarWriter, entry_name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    header.set_mtime(now);
+    tar.append_data(&mut header, entry_name, data)?;
+    Ok(())
+}
+
+/// Streams a file into the tar archive with the given `mode`, teeing its bytes
+/// into a new zip entry when a zip writer and its entry options are given, so
+/// the file is read only once.
+///
+/// Returns the size of the file in bytes, and its digest when `algorithm` is set.
+pub(crate) fn append_tar_file<W: Write + Seek>(
+    tar: &mut TarWriter,
+    zip: Option<(&mut ZipWriter<W>, FullFileOptions<'static>)>,
+    path: &Path,
+    entry_name: &str,
+    mode: u32,
+    throttle: Option<&mut TokenBucket>,
+    algorithm: Option<ChecksumAlgorithm>,
+) -> Result<(u64, Option<String>)> {
+    let f = File::open(path)?;
+    let metadata = f.metadata()?;
+    let mut header = tar_header(&metadata, mode);
+
+    // The tar header announces the size up front, never read past it
+    let mut reader =
+        HashingReader::new(Throttled::new(f.take(metadata.len()), throttle), algorithm);
+
+    match zip {
+        Some((zip, options)) => {
+            zip.start_file(entry_name, options)?;
+            let tee = TeeReader {
+                inner: &mut reader,
+                sink: zip,
+            };
+            tar.append_data(&mut header, entry_name, tee)?;
+        }
+        None => tar.append_data(&mut header, entry_name, &mut reader)?,
+    }
+
+    Ok((metadata.len(), reader.finish()))
+}
+
+/// Reader adapter that copies every byte it reads into a second writer.
+pub(crate) struct TeeReader<R, W> {
+    pub(crate) inner: R,
+    pub(crate) sink: W,
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.sink.write_all(&buf[..read])?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_files, ScanConfig};
+    use flate2::read::GzDecoder;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_default_output_stem() {
+        let project = Path::new("/home/dev/my-app");
+        assert_eq!(default_output_stem(project, OutputNaming::Dir), "my-app");
+        assert_eq!(default_output_stem(project, OutputNaming::Fixed), "archive");
+
+        // A filesystem root has no name to derive one from
+        assert_eq!(
+            default_output_stem(Path::new("/"), OutputNaming::Dir),
+            "archive"
+        );
+        assert_eq!(
+            default_output_stem(&Path::new("/").canonicalize().unwrap(), OutputNaming::Dir),
+            "archive"
+        );
+    }
+
+    #[test]
+    fn test_temp_output_path_is_a_fresh_absolute_file() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+
+        let output = temp_output_path("project", "zip").unwrap();
+        let other = temp_output_path("project", "zip").unwrap();
+        assert_ne!(output, other);
+        assert!(output.is_absolute());
+        assert!(output.starts_with(std::path::absolute(std::env::temp_dir()).unwrap()));
+        let name = output.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(
+            name.starts_with("project-") && name.ends_with(".zip"),
+            "{}",
+            name
+        );
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        pack_files(&files, &PackConfig::new(&root, &output), |_, _, _| {}).unwrap();
+        let archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), vec!["main.rs"]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&output).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        std::fs::remove_file(output).unwrap();
+        std::fs::remove_file(other).unwrap();
+    }
+
+    #[test]
+    fn test_pack_zip_and_tar_gz_in_one_call() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let config = PackConfig::new(&root, temp_dir.path().join("release.zip"));
+
+        let outputs = pack_files_multi(
+            &files,
+            &config,
+            &[ArchiveFormat::Zip, ArchiveFormat::TarGz],
+            |_, _, _| {},
+        )
+        .expect("Packing failed")
+        .outputs;
+        assert_eq!(
+            outputs,
+            vec![
+                temp_dir.path().join("release.zip"),
+                temp_dir.path().join("release.tar.gz")
+            ]
+        );
+
+        // The zip is valid and complete
+        let mut archive = ZipArchive::new(File::open(&outputs[0]).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut main_rs = String::new();
+        archive
+            .by_name("src/main.rs")
+            .unwrap()
+            .read_to_string(&mut main_rs)
+            .unwrap();
+        assert_eq!(main_rs, "fn main() {}");
+
+        // The tar.gz is valid and holds the same content
+        let mut tar = tar::Archive::new(GzDecoder::new(File::open(&outputs[1]).unwrap()));
+        let mut entries = Vec::new();
+        for entry in tar.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().into_owned();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            entries.push((name, content));
+        }
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                ("README.md".to_string(), "# Hello".to_string()),
+                ("src/main.rs".to_string(), "fn main() {}".to_string()),
+            ]
+        );
+    }
+}
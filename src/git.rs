@@ -0,0 +1,333 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Keeps only the files tracked by git, as listed by `git ls-files`.
+///
+/// Untracked files are dropped even when no ignore rule excludes them.
+/// Directory entries are kept when they contain a tracked file.
+///
+/// # Arguments
+///
+/// * `files` - The scanned paths, under the canonical root like those of
+///   [`scan_files`](crate::scan_files).
+/// * `root_path` - The scan root, in any form; it must be inside a git work tree.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{retain_tracked, scan_files, ScanConfig};
+/// use std::path::Path;
+///
+/// let root = Path::new(".");
+/// let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+/// let files = retain_tracked(files, root).unwrap();
+/// ```
+pub fn retain_tracked(files: Vec<PathBuf>, root_path: &Path) -> Result<Vec<PathBuf>> {
+    let tracked = tracked_files(root_path)?;
+
+    // Parent directories of tracked files, for directory entries
+    let tracked_dirs: HashSet<&Path> = tracked
+        .iter()
+        .flat_map(|path| path.ancestors().skip(1))
+        .collect();
+
+    Ok(files
+        .into_iter()
+        .filter(|path| tracked.contains(path) || tracked_dirs.contains(path.as_path()))
+        .collect())
+}
+
+/// Adds the files tracked by the git submodules under `root_path`, nested ones
+/// included.
+///
+/// `git ls-files` lists a submodule as a single entry, so [`retain_tracked`]
+/// drops its files; ignore rules can hide them too. Submodules that aren't
+/// initialized have nothing to pack and are skipped with a warning. Files
+/// already in the list are not added twice.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{add_submodule_files, retain_tracked, scan_files, ScanConfig};
+/// use std::path::Path;
+///
+/// let root = Path::new(".").canonicalize().unwrap();
+/// let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+/// let files = add_submodule_files(retain_tracked(files, &root).unwrap(), &root).unwrap();
+/// ```
+pub fn add_submodule_files(mut files: Vec<PathBuf>, root_path: &Path) -> Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root_path)
+        .args(["submodule", "status", "--recursive"])
+        .output()
+        .context("Failed to run git (is it installed?)")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            anyhow::bail!(
+                "{:?} is not inside a git repository, it has no submodules",
+                root_path
+            );
+        }
+        anyhow::bail!("git submodule status failed: {}", stderr.trim());
+    }
+
+    let mut seen: HashSet<PathBuf> = files.iter().cloned().collect();
+    // Each line is `<state><commit> <path>[ (<describe>)]`, the path relative
+    // to the directory git ran in
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some((commit, rest)) = line.get(1..).and_then(|line| line.split_once(' ')) else {
+            continue;
+        };
+        let path = rest.rsplit_once(" (").map_or(rest, |(path, _)| path);
+        if path.starts_with("../") {
+            continue;
+        }
+        if line.starts_with('-') {
+            eprintln!(
+                "Scan warning: skipping submodule {:?} ({}), it is not initialized",
+                path,
+                &commit[..commit.len().min(12)]
+            );
+            continue;
+        }
+
+        let mut submodule_files: Vec<PathBuf> = tracked_files(&root_path.join(path))?
+            .into_iter()
+            .filter(|file| file.is_file() && !seen.contains(file))
+            .collect();
+        submodule_files.sort();
+        seen.extend(submodule_files.iter().cloned());
+        files.extend(submodule_files);
+    }
+
+    Ok(files)
+}
+
+/// Returns the top-level directory of the git work tree containing `path`.
+///
+/// Packing with it as the [`PackConfig::root_path`](crate::PackConfig::root_path)
+/// names the entries of a scanned subdirectory as they appear in the repository.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{git_root, pack_files, scan_files, PackConfig, ScanConfig};
+///
+/// // Entries are named `src/...`, not relative to `src` itself
+/// let files = scan_files(&ScanConfig::new("src", vec![])).unwrap();
+/// let config = PackConfig::new(git_root("src".as_ref()).unwrap(), "src.zip");
+/// pack_files(&files, &config, |_, _, _| {}).unwrap();
+/// ```
+pub fn git_root(path: &Path) -> Result<PathBuf> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(path)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .context("Failed to run git (is it installed?)")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            anyhow::bail!(
+                "{:?} is not inside a git repository, entries can't be named from its root",
+                path
+            );
+        }
+        anyhow::bail!("git rev-parse failed: {}", stderr.trim());
+    }
+
+    let top_level = String::from_utf8_lossy(&output.stdout);
+    let top_level = Path::new(top_level.trim_end_matches(['\n', '\r']));
+    top_level
+        .canonicalize()
+        .with_context(|| format!("Cannot access the git root: {:?}", top_level))
+}
+
+/// Lists the files of the git index under `root_path`, as absolute paths
+/// under the canonical root, to compare with the scanned paths.
+fn tracked_files(root_path: &Path) -> Result<HashSet<PathBuf>> {
+    let root_path = &root_path
+        .canonicalize()
+        .with_context(|| format!("Cannot access directory: {:?}", root_path))?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root_path)
+        .args(["ls-files", "-z"])
+        .output()
+        .context("Failed to run git (is it installed?)")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            anyhow::bail!(
+                "{:?} is not inside a git repository, it has no tracked files",
+                root_path
+            );
+        }
+        anyhow::bail!("git ls-files failed: {}", stderr.trim());
+    }
+
+    // Paths are NUL separated and relative to the directory git ran in
+    Ok(output
+        .stdout
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| root_path.join(String::from_utf8_lossy(name).as_ref()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use std::fs::File;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    fn git(root: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_retain_tracked_drops_untracked_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("repo");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("scratch.txt"), "untracked cruft").unwrap();
+
+        // Outside of a repository, the error says so
+        let err = retain_tracked(vec![], &root).unwrap_err();
+        assert!(err.to_string().contains("not inside a git repository"));
+
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "src/main.rs"]);
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        assert!(files.iter().any(|path| path.ends_with("scratch.txt")));
+        let files = retain_tracked(files, &root).unwrap();
+        assert_eq!(files.len(), 1);
+
+        // The root may be given through a symlink
+        #[cfg(unix)]
+        {
+            let link = temp_dir.path().join("link");
+            std::os::unix::fs::symlink(&root, &link).unwrap();
+            let scanned = scan_files(&ScanConfig::new(&link, vec![])).unwrap();
+            assert_eq!(retain_tracked(scanned, &link).unwrap(), files);
+        }
+
+        let output_zip_path = temp_dir.path().join("tracked.zip");
+        pack_files(
+            &files,
+            &PackConfig::new(&root, &output_zip_path),
+            |_, _, _| {},
+        )
+        .unwrap();
+        let archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            vec!["src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_submodule_files_are_packed_under_their_path() {
+        let temp_dir = tempdir().unwrap();
+        let base = temp_dir.path().canonicalize().unwrap();
+        let identity = [
+            "-c",
+            "user.name=srcpack",
+            "-c",
+            "user.email=srcpack@example.com",
+        ];
+        let commit = |root: &Path| {
+            git(root, &["add", "."]);
+            git(
+                root,
+                &[&identity[..], &["commit", "-q", "-m", "init"]].concat(),
+            );
+        };
+
+        for name in ["lib", "docs"] {
+            let repo = base.join(name);
+            std::fs::create_dir_all(repo.join("src")).unwrap();
+            std::fs::write(repo.join("src/lib.c"), "int answer = 42;").unwrap();
+            git(&repo, &["init", "-q"]);
+            commit(&repo);
+        }
+        let root = base.join("app");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.c"), "int main() {}").unwrap();
+        git(&root, &["init", "-q"]);
+        for name in ["lib", "docs"] {
+            let source = base.join(name);
+            let source = source.to_str().unwrap();
+            let add = [
+                "-c",
+                "protocol.file.allow=always",
+                "submodule",
+                "add",
+                "-q",
+                source,
+            ];
+            git(&root, &[&add[..], &[&format!("vendor/{}", name)]].concat());
+        }
+        commit(&root);
+        git(&root, &["submodule", "deinit", "-q", "-f", "vendor/docs"]);
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let files = retain_tracked(files, &root).unwrap();
+        assert!(!files.contains(&root.join("vendor/lib/src/lib.c")));
+        let files = add_submodule_files(files, &root).unwrap();
+
+        let output_zip_path = temp_dir.path().join("app.zip");
+        pack_files(
+            &files,
+            &PackConfig::new(&root, &output_zip_path),
+            |_, _, _| {},
+        )
+        .unwrap();
+        let archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec![".gitmodules", "main.c", "vendor/lib/src/lib.c"]);
+    }
+
+    #[test]
+    fn test_git_root_names_entries_from_the_repository() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("repo");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let root = root.canonicalize().unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+
+        let err = git_root(&root.join("src")).unwrap_err();
+        assert!(err.to_string().contains("not inside a git repository"));
+
+        git(&root, &["init", "-q"]);
+        assert_eq!(git_root(&root.join("src")).unwrap(), root);
+
+        let files = scan_files(&ScanConfig::new(root.join("src"), vec![])).unwrap();
+        let output_zip_path = temp_dir.path().join("src.zip");
+        let config = PackConfig::new(git_root(&root.join("src")).unwrap(), &output_zip_path);
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            vec!["src/main.rs"]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Matches paths against the `export-ignore` attribute of `.gitattributes` files.
+///
+/// Only that attribute is understood: `export-ignore` sets it, while
+/// `-export-ignore` and `!export-ignore` clear a setting made by an earlier
+/// line or a parent directory. As with git, the last matching line wins and
+/// deeper `.gitattributes` files override shallower ones.
+pub(crate) struct ExportIgnore {
+    root: PathBuf,
+    /// Parsed `.gitattributes` per directory (`None` when there is none).
+    dirs: Mutex<HashMap<PathBuf, Option<Gitignore>>>,
+}
+
+impl ExportIgnore {
+    pub(crate) fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            dirs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns true if the path is marked `export-ignore`.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut dirs = self.dirs.lock().unwrap();
+
+        for dir in path.ancestors().skip(1) {
+            if !dir.starts_with(&self.root) {
+                break;
+            }
+            let matcher = dirs
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| parse_gitattributes(dir));
+            match matcher.as_ref().map(|m| m.matched(path, is_dir)) {
+                Some(Match::Ignore(_)) => return true,
+                Some(Match::Whitelist(_)) => return false,
+                _ => {}
+            }
+        }
+
+        false
+    }
+}
+
+/// Parses the `export-ignore` rules of a directory's `.gitattributes`.
+///
+/// The rules are kept in a gitignore matcher, which shares the pattern syntax
+/// of attributes files: set attributes become ignore rules, unset ones
+/// become whitelist rules.
+fn parse_gitattributes(dir: &Path) -> Option<Gitignore> {
+    let path = dir.join(".gitattributes");
+    let content = std::fs::read_to_string(&path).ok()?;
+    let mut builder = GitignoreBuilder::new(dir);
+
+    for line in content.lines() {
+        let line = line.trim();
+        // Comments, and macro definitions which can't be used for export-ignore
+        if line.is_empty() || line.starts_with('#') || line.starts_with("[attr]") {
+            continue;
+        }
+
+        let (pattern, attributes) = split_pattern(line);
+        // Negative patterns are forbidden in attributes files
+        if pattern.is_empty() || pattern.starts_with('!') {
+            continue;
+        }
+
+        let state = attributes.split_whitespace().fold(None, |state, attr| {
+            match attr.trim_start_matches(['-', '!']) {
+                "export-ignore" => Some(!attr.starts_with(['-', '!'])),
+                _ => state,
+            }
+        });
+        let rule = match state {
+            Some(true) => pattern.to_string(),
+            Some(false) => format!("!{}", pattern),
+            None => continue,
+        };
+        let _ = builder.add_line(Some(path.clone()), &rule);
+    }
+
+    builder.build().ok().filter(|m| !m.is_empty())
+}
+
+/// Splits an attributes line into its pattern and attribute list.
+///
+/// The pattern may be double-quoted to contain spaces.
+fn split_pattern(line: &str) -> (String, &str) {
+    if let Some(quoted) = line.strip_prefix('"') {
+        if let Some(end) = quoted.find('"') {
+            let pattern = quoted[..end].replace("\\\"", "\"").replace("\\\\", "\\");
+            return (pattern, &quoted[end + 1..]);
+        }
+    }
+    match line.split_once(char::is_whitespace) {
+        Some((pattern, attributes)) => (pattern.to_string(), attributes),
+        None => (line.to_string(), ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{scan_files, ScanConfig};
+    use std::fs::{create_dir_all, write};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_ignore_excludes_marked_paths() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        create_dir_all(root.join("docs")).unwrap();
+        create_dir_all(root.join("src/fixtures")).unwrap();
+
+        write(
+            root.join(".gitattributes"),
+            "# release tarball\ndocs/ export-ignore\n*.rs text eol=lf\n",
+        )
+        .unwrap();
+        write(
+            root.join("src/.gitattributes"),
+            "fixtures/** export-ignore\nfixtures/keep.txt -export-ignore\n",
+        )
+        .unwrap();
+        write(root.join("docs/guide.md"), "# Guide").unwrap();
+        write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        write(root.join("src/fixtures/big.bin"), "data").unwrap();
+        write(root.join("src/fixtures/keep.txt"), "keep").unwrap();
+
+        let names = |config: &ScanConfig| {
+            let mut names: Vec<String> = scan_files(config)
+                .unwrap()
+                .iter()
+                .map(|p| crate::relative_entry_name(p, root))
+                .collect();
+            names.sort();
+            names
+        };
+
+        // The option is off by default
+        let mut config = ScanConfig::new(root, vec![]);
+        assert!(names(&config).contains(&"docs/guide.md".to_string()));
+
+        config.export_ignore = true;
+        assert_eq!(
+            names(&config),
+            vec![
+                ".gitattributes",
+                "src/.gitattributes",
+                "src/fixtures/keep.txt",
+                "src/main.rs"
+            ]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::{hash_file, named_inputs, Attachment, ChecksumAlgorithm};
+
+/// A difference between the scanned files and a lockfile, see [`check_lockfile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockDrift {
+    /// A file not recorded in the lockfile.
+    Added(String),
+    /// A recorded file that is no longer scanned.
+    Removed(String),
+    /// A recorded file whose content changed.
+    Changed(String),
+}
+
+impl fmt::Display for LockDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockDrift::Added(path) => write!(f, "added: {}", path),
+            LockDrift::Removed(path) => write!(f, "removed: {}", path),
+            LockDrift::Changed(path) => write!(f, "changed: {}", path),
+        }
+    }
+}
+
+/// Hash and size of every file, by relative path, and of every attachment,
+/// by entry name. The lockfile itself is left out.
+fn lock_entries(
+    files: &[PathBuf],
+    root_path: &Path,
+    attachments: &[Attachment],
+    lock_path: &Path,
+) -> Result<BTreeMap<String, (String, u64)>> {
+    let lock_path = lock_path.canonicalize().ok();
+    let mut entries = BTreeMap::new();
+    for (path, name) in named_inputs(files, root_path, attachments) {
+        if path.is_dir() || lock_path.as_deref() == Some(path.as_path()) {
+            continue;
+        }
+        let size = std::fs::metadata(path)
+            .with_context(|| format!("Failed to read metadata: {:?}", path))?
+            .len();
+        let digest = hash_file(path, ChecksumAlgorithm::Sha256)?;
+        entries.insert(name, (digest, size));
+    }
+    Ok(entries)
+}
+
+/// Writes a lockfile recording the exact set of files and their content.
+///
+/// Each line holds the relative path, the SHA-256 of the file and its size,
+/// sorted by path:
+///
+/// ```text
+/// src/main.rs 9f86d081884c7d659a2feaa0c55ad015... 1024
+/// ```
+///
+/// Directory entries are left out, as is the lockfile if it is under the root.
+/// `attachments` (e.g. the files of the roots of a
+/// [`scan_roots`](crate::scan_roots) list) are recorded under their entry
+/// names.
+pub fn write_lockfile(
+    files: &[PathBuf],
+    root_path: &Path,
+    attachments: &[Attachment],
+    lock_path: &Path,
+) -> Result<()> {
+    let mut content = String::new();
+    for (name, (digest, size)) in lock_entries(files, root_path, attachments, lock_path)? {
+        content.push_str(&format!("{} {} {}\n", name, digest, size));
+    }
+    std::fs::write(lock_path, content)
+        .with_context(|| format!("Failed to write lockfile: {:?}", lock_path))
+}
+
+/// Compares the files with a lockfile written by [`write_lockfile`].
+///
+/// Returns the differences, sorted by path; an empty list means the files
+/// match the lockfile exactly.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{check_lockfile, scan_files, ScanConfig};
+/// use std::path::Path;
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// for drift in check_lockfile(&files, Path::new("."), &[], Path::new("srcpack.lock")).unwrap() {
+///     println!("{}", drift); // changed: src/main.rs
+/// }
+/// ```
+pub fn check_lockfile(
+    files: &[PathBuf],
+    root_path: &Path,
+    attachments: &[Attachment],
+    lock_path: &Path,
+) -> Result<Vec<LockDrift>> {
+    let content = std::fs::read_to_string(lock_path)
+        .with_context(|| format!("Failed to read lockfile: {:?}", lock_path))?;
+
+    let mut locked = BTreeMap::new();
+    for (number, line) in content.lines().enumerate() {
+        // The path may contain spaces, the hash and size can't
+        let mut fields = line.rsplitn(3, ' ');
+        let (Some(size), Some(digest), Some(name)) = (fields.next(), fields.next(), fields.next())
+        else {
+            anyhow::bail!("Invalid lockfile line {}: {:?}", number + 1, line);
+        };
+        let size: u64 = size
+            .parse()
+            .with_context(|| format!("Invalid size on lockfile line {}", number + 1))?;
+        locked.insert(name.to_string(), (digest.to_string(), size));
+    }
+
+    let current = lock_entries(files, root_path, attachments, lock_path)?;
+    let mut drift = Vec::new();
+    for (name, entry) in &current {
+        match locked.get(name) {
+            None => drift.push(LockDrift::Added(name.clone())),
+            Some(locked_entry) if locked_entry != entry => {
+                drift.push(LockDrift::Changed(name.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    for name in locked.keys().filter(|name| !current.contains_key(*name)) {
+        drift.push(LockDrift::Removed(name.clone()));
+    }
+    drift.sort_by(|a, b| drift_path(a).cmp(drift_path(b)));
+    Ok(drift)
+}
+
+fn drift_path(drift: &LockDrift) -> &str {
+    match drift {
+        LockDrift::Added(path) | LockDrift::Removed(path) | LockDrift::Changed(path) => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_files, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lockfile_detects_drift() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("my notes.md"), b"# Notes").unwrap();
+
+        // The lockfile lives in the root, and doesn't lock itself
+        let lock_path = root.join("srcpack.lock");
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        write_lockfile(&files, &root, &[], &lock_path).unwrap();
+        let content = std::fs::read_to_string(&lock_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("my notes.md "));
+        assert!(lines[1].starts_with("src/main.rs "));
+        assert!(lines[1].ends_with(" 12"));
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        assert_eq!(
+            check_lockfile(&files, &root, &[], &lock_path).unwrap(),
+            vec![]
+        );
+
+        std::fs::write(root.join("src/main.rs"), b"fn main() { drift() }").unwrap();
+        std::fs::remove_file(root.join("my notes.md")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), b"").unwrap();
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        assert_eq!(
+            check_lockfile(&files, &root, &[], &lock_path).unwrap(),
+            vec![
+                LockDrift::Removed("my notes.md".to_string()),
+                LockDrift::Added("src/lib.rs".to_string()),
+                LockDrift::Changed("src/main.rs".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::{full_archive_entries, PackConfig};
+
+/// Longest path component, in bytes, most filesystems and unzip tools accept.
+/// Default of [`PackConfig::max_component_len`].
+pub const MAX_COMPONENT_LEN: usize = 255;
+
+/// Default of [`PackConfig::max_name_len`].
+pub const DEFAULT_MAX_NAME_LEN: usize = 1024;
+
+/// First line of the archive comment listing the names shortened by
+/// [`LongNamePolicy::Truncate`].
+const COMMENT_HEADER: &str = "srcpack: shortened entry names (short<TAB>full)";
+
+/// What to do with entry names longer than [`PackConfig::max_name_len`], or
+/// with a component longer than [`PackConfig::max_component_len`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LongNamePolicy {
+    /// Names are written as is; [`find_long_names`] lists them.
+    #[default]
+    Warn,
+    /// Packing fails before anything is written.
+    Error,
+    /// Names are cut down and suffixed with a hash of the full name, keeping
+    /// the extension. Zip archives list the full names in the archive comment.
+    Truncate,
+}
+
+impl fmt::Display for LongNamePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LongNamePolicy::Warn => "warn",
+            LongNamePolicy::Error => "error",
+            LongNamePolicy::Truncate => "truncate",
+        })
+    }
+}
+
+impl FromStr for LongNamePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "warn" => Ok(LongNamePolicy::Warn),
+            "error" => Ok(LongNamePolicy::Error),
+            "truncate" => Ok(LongNamePolicy::Truncate),
+            _ => anyhow::bail!(
+                "Unknown long name policy {:?} (expected warn, error or truncate)",
+                s
+            ),
+        }
+    }
+}
+
+/// Returns true if a name is too long for the config, as a whole or in one
+/// of its components. Lengths are in UTF-8 bytes.
+fn is_too_long(name: &str, config: &PackConfig) -> bool {
+    name.len() > config.max_name_len || name.split('/').any(|c| c.len() > config.max_component_len)
+}
+
+/// Returns the entry names (prefix and normalization applied) that are too
+/// long for the config, in archive order, before any truncation.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{find_long_names, scan_files, PackConfig, ScanConfig};
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// for name in find_long_names(&files, &PackConfig::new(".", "archive.zip")) {
+///     println!("Long entry name: {}", name);
+/// }
+/// ```
+pub fn find_long_names(files: &[PathBuf], config: &PackConfig) -> Vec<String> {
+    full_archive_entries(files, config)
+        .map(|(_, name)| name)
+        .filter(|name| is_too_long(name, config))
+        .collect()
+}
+
+/// Applies [`LongNamePolicy::Truncate`] to an entry name, leaving names that
+/// fit untouched.
+pub(crate) fn fit_name(name: String, config: &PackConfig) -> String {
+    if config.long_names != LongNamePolicy::Truncate || !is_too_long(&name, config) {
+        return name;
+    }
+
+    // Over-long components first, each hashed with the path leading to it so
+    // files of the same long directory stay together, then the name as a whole
+    let mut prefix_len = 0;
+    let components: Vec<String> = name
+        .split('/')
+        .map(|c| {
+            prefix_len += c.len();
+            let hash = short_hash(&name[..prefix_len]);
+            prefix_len += 1;
+            shorten(c, config.max_component_len, &hash)
+        })
+        .collect();
+    shorten(
+        &components.join("/"),
+        config.max_name_len,
+        &short_hash(&name),
+    )
+}
+
+/// Cuts `s` to `max_len` bytes, ending in `~<hash>` and its extension.
+fn shorten(s: &str, max_len: usize, hash: &str) -> String {
+    if s.len() <= max_len {
+        return s.to_string();
+    }
+    let file_name = s.rsplit('/').next().unwrap_or(s);
+    let extension = match file_name.rfind('.') {
+        // Not a dotfile, and a plausible extension
+        Some(dot) if dot > 0 && file_name.len() - dot <= 16 => &file_name[dot..],
+        _ => "",
+    };
+    let suffix = format!("~{}{}", hash, extension);
+
+    let mut end = max_len.saturating_sub(suffix.len());
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", s[..end].trim_end_matches('/'), suffix)
+}
+
+/// First 8 hex digits of the SHA-256 of a name or path.
+fn short_hash(name: &str) -> String {
+    Sha256::digest(name.as_bytes())
+        .iter()
+        .take(4)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Builds the archive comment mapping shortened names to full names, or
+/// `None` if nothing was shortened.
+pub(crate) fn long_names_comment(files: &[PathBuf], config: &PackConfig) -> Option<String> {
+    if config.long_names != LongNamePolicy::Truncate {
+        return None;
+    }
+    let long = find_long_names(files, config);
+    if long.is_empty() {
+        return None;
+    }
+
+    let mut comment = String::from(COMMENT_HEADER);
+    for name in long {
+        comment.push('\n');
+        comment.push_str(&fit_name(name.clone(), config));
+        comment.push('\t');
+        comment.push_str(&name);
+    }
+    Some(comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, ScanConfig};
+    use std::fs::File;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_long_name_policies() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        let deep: PathBuf = (0..12)
+            .map(|i| format!("nested-directory-{:02}", i))
+            .collect();
+        std::fs::create_dir_all(root.join(&deep)).unwrap();
+        std::fs::write(root.join(&deep).join("module.rs"), b"mod deep;").unwrap();
+        std::fs::write(root.join("short.rs"), b"mod short;").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let full_name = format!("{}/module.rs", deep.to_string_lossy());
+        let mut config = PackConfig::new(&root, temp_dir.path().join("warn.zip"));
+        config.max_name_len = 100;
+        assert!(full_name.len() > 100);
+        assert_eq!(find_long_names(&files, &config), vec![full_name.clone()]);
+
+        // Warn: the name is kept
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        assert!(archive.file_names().any(|n| n == full_name));
+
+        // Error: packing fails
+        config.long_names = LongNamePolicy::Error;
+        config.output_path = temp_dir.path().join("error.zip");
+        std::fs::write(&config.output_path, b"previous archive").unwrap();
+        let err = pack_files(&files, &config, |_, _, _| {}).unwrap_err();
+        assert!(err.to_string().contains(&full_name));
+        // Nothing was written: the existing file is left alone
+        assert_eq!(
+            std::fs::read(&config.output_path).unwrap(),
+            b"previous archive"
+        );
+
+        // Truncate: the name fits, keeps its extension, and the comment has the full name
+        config.long_names = LongNamePolicy::Truncate;
+        config.output_path = temp_dir.path().join("truncate.zip");
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let mut archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[1], "short.rs");
+        let short = &names[0];
+        assert!(short.len() <= 100);
+        assert!(short.starts_with("nested-directory-00/"));
+        assert!(short.ends_with(".rs"));
+        let comment = String::from_utf8(archive.comment().to_vec()).unwrap();
+        assert!(comment.contains(&format!("{}\t{}", short, full_name)));
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name(short).unwrap(), &mut content).unwrap();
+        assert_eq!(content, "mod deep;");
+    }
+
+    #[test]
+    fn test_component_limit_counts_bytes() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        // 100 characters, 200 bytes
+        let component = "é".repeat(100);
+        std::fs::create_dir_all(root.join(&component)).unwrap();
+        std::fs::write(root.join(&component).join("notes.md"), b"# Notes").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("warn.zip"));
+        assert!(find_long_names(&files, &config).is_empty());
+
+        config.max_component_len = 150;
+        let name = format!("{}/notes.md", component);
+        assert_eq!(find_long_names(&files, &config), vec![name.clone()]);
+        // Warn: packed as is
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        assert!(archive.file_names().any(|n| n == name));
+
+        // Error: packing fails
+        config.long_names = LongNamePolicy::Error;
+        config.output_path = temp_dir.path().join("error.zip");
+        let err = pack_files(&files, &config, |_, _, _| {}).unwrap_err();
+        assert!(err.to_string().contains("150 per component"), "{}", err);
+        assert!(!config.output_path.exists());
+    }
+
+    #[test]
+    fn test_truncate_keeps_directories_together() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        let long_dir = "d".repeat(80);
+        std::fs::create_dir_all(root.join(&long_dir)).unwrap();
+        std::fs::write(root.join(&long_dir).join("a.rs"), b"mod a;").unwrap();
+        std::fs::write(root.join(&long_dir).join("b.rs"), b"mod b;").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        config.max_component_len = 40;
+        config.long_names = LongNamePolicy::Truncate;
+        let a = fit_name(format!("{}/a.rs", long_dir), &config);
+        let b = fit_name(format!("{}/b.rs", long_dir), &config);
+        let (a_dir, a_file) = a.split_once('/').unwrap();
+        let (b_dir, b_file) = b.split_once('/').unwrap();
+        assert!(a_dir.len() <= 40);
+        assert_eq!(a_dir, b_dir);
+        assert_eq!((a_file, b_file), ("a.rs", "b.rs"));
+
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec![a.as_str(), b.as_str()]);
+    }
+}
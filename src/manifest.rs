@@ -0,0 +1,409 @@
+use anyhow::{Context, Result};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::{relative_entry_name, Phase};
+
+/// Hash algorithms supported for checksum manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumAlgorithm {
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    /// The lowercase name of the algorithm, also used as the sidecar extension.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+            ChecksumAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// The tag written in front of each manifest line (BSD `--tag` style).
+    fn tag(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha1 => "SHA1",
+            ChecksumAlgorithm::Sha256 => "SHA256",
+            ChecksumAlgorithm::Sha512 => "SHA512",
+            ChecksumAlgorithm::Blake3 => "BLAKE3",
+        }
+    }
+}
+
+impl fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha1" => Ok(ChecksumAlgorithm::Sha1),
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            "sha512" => Ok(ChecksumAlgorithm::Sha512),
+            "blake3" => Ok(ChecksumAlgorithm::Blake3),
+            _ => anyhow::bail!(
+                "Unknown checksum algorithm {:?} (expected sha1, sha256, sha512 or blake3)",
+                s
+            ),
+        }
+    }
+}
+
+/// Incremental hasher dispatching to the selected algorithm.
+pub(crate) enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            ChecksumAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    /// Consumes the hasher and returns the lowercase hex digest.
+    pub(crate) fn finalize_hex(self) -> String {
+        match self {
+            Hasher::Sha1(h) => to_hex(&h.finalize()),
+            Hasher::Sha256(h) => to_hex(&h.finalize()),
+            Hasher::Sha512(h) => to_hex(&h.finalize()),
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Reader adapter that hashes every byte it reads, so a file can be digested
+/// while it is being packed.
+pub(crate) struct HashingReader<R> {
+    inner: R,
+    hasher: Option<Hasher>,
+}
+
+impl<R: Read> HashingReader<R> {
+    /// Hashes with `algorithm`, or just passes the bytes through when `None`.
+    pub(crate) fn new(inner: R, algorithm: Option<ChecksumAlgorithm>) -> Self {
+        HashingReader {
+            inner,
+            hasher: algorithm.map(Hasher::new),
+        }
+    }
+
+    /// Returns the hex digest of everything read so far.
+    pub(crate) fn finish(self) -> Option<String> {
+        self.hasher.map(Hasher::finalize_hex)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+/// The digest of a file, computed while it was packed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDigest {
+    pub path: PathBuf,
+    /// The name of its entry in the archive.
+    pub entry_name: String,
+    /// The lowercase hex digest, with [`PackConfig::hash_files`](crate::PackConfig::hash_files).
+    pub digest: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Computes the hex digest of a file's content with the given algorithm.
+pub fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> Result<String> {
+    hash_file_sized(path, algorithm).map(|(digest, _)| digest)
+}
+
+/// Like [`hash_file`], also returning the number of bytes hashed.
+fn hash_file_sized(path: &Path, algorithm: ChecksumAlgorithm) -> Result<(String, u64)> {
+    let mut f = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut size = 0;
+
+    loop {
+        let read = f.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((hasher.finalize_hex(), size))
+}
+
+/// Writes a checksum manifest listing the digest of every packed file.
+///
+/// Each line uses the BSD tag format, which records the algorithm alongside
+/// the digest and can be checked with e.g. `sha256sum -c`:
+///
+/// ```text
+/// SHA256 (src/main.rs) = 9f86d081884c7d659a2feaa0c55ad015...
+/// ```
+///
+/// # Arguments
+///
+/// * `files` - The packed file paths.
+/// * `root_path` - The base path used to calculate relative paths.
+/// * `manifest_path` - The destination of the manifest sidecar.
+/// * `algorithm` - The hash algorithm to use.
+pub fn write_manifest(
+    files: &[PathBuf],
+    root_path: &Path,
+    manifest_path: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> Result<()> {
+    write_manifest_with_progress(files, root_path, manifest_path, algorithm, |_, _, _| {})
+}
+
+/// Like [`write_manifest`], calling `on_progress` with [`Phase::Hashing`], the
+/// path and the number of bytes hashed so far after every file.
+pub fn write_manifest_with_progress<F>(
+    files: &[PathBuf],
+    root_path: &Path,
+    manifest_path: &Path,
+    algorithm: ChecksumAlgorithm,
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(Phase, &Path, u64),
+{
+    let file = File::create(manifest_path)
+        .with_context(|| format!("Failed to create manifest: {:?}", manifest_path))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut hashed = 0;
+    // Directory entries have no content to hash
+    for path in files.iter().filter(|p| !p.is_dir()) {
+        let (digest, size) = hash_file_sized(path, algorithm)?;
+        hashed += size;
+        write_manifest_line(
+            &mut writer,
+            algorithm,
+            &relative_entry_name(path, root_path),
+            &digest,
+        )?;
+        on_progress(Phase::Hashing, path, hashed);
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes a checksum manifest from the digests computed while packing (see
+/// [`PackConfig::hash_files`](crate::PackConfig::hash_files)), without reading
+/// the files again. The result is the same as [`write_manifest`]'s, with the
+/// attachments listed too: by their path relative to the root, or their full
+/// path when they are outside of it.
+pub fn write_manifest_digests(
+    digests: &[FileDigest],
+    root_path: &Path,
+    manifest_path: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> Result<()> {
+    let file = File::create(manifest_path)
+        .with_context(|| format!("Failed to create manifest: {:?}", manifest_path))?;
+    let mut writer = BufWriter::new(file);
+
+    for file_digest in digests {
+        let name = relative_entry_name(&file_digest.path, root_path);
+        write_manifest_line(&mut writer, algorithm, &name, &file_digest.digest)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_manifest_line(
+    writer: &mut impl Write,
+    algorithm: ChecksumAlgorithm,
+    name: &str,
+    digest: &str,
+) -> std::io::Result<()> {
+    writeln!(writer, "{} ({}) = {}", algorithm.tag(), name, digest)
+}
+
+/// Returns the default manifest location for an archive: the archive path
+/// with the algorithm name appended (e.g. `project.zip.sha256`).
+pub fn manifest_path_for(output_path: &Path, algorithm: ChecksumAlgorithm) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(algorithm.name());
+    PathBuf::from(name)
+}
+
+/// Renames an archive after the BLAKE3 digest of its content, for
+/// content-addressed storage: `<dir>/<hexdigest>.<extension>`.
+///
+/// Returns the new path. An existing file of that name holds the same
+/// content, and is replaced.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::content_address;
+/// use std::path::Path;
+///
+/// let stored = content_address(Path::new("out/backup.zip"), "zip").unwrap();
+/// println!("Stored as {}", stored.display()); // out/af1349b9f5f9a1a6....zip
+/// ```
+pub fn content_address(archive_path: &Path, extension: &str) -> Result<PathBuf> {
+    let digest = hash_file(archive_path, ChecksumAlgorithm::Blake3)?;
+    let extension = extension.trim_start_matches('.');
+    let name = match extension.is_empty() {
+        true => digest,
+        false => format!("{}.{}", digest, extension),
+    };
+
+    let target = archive_path.with_file_name(name);
+    std::fs::rename(archive_path, &target)
+        .with_context(|| format!("Failed to rename {:?} to {:?}", archive_path, target))?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_digest_matches_reference_implementations() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        let path = root.join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+
+        // Well-known test vectors for "abc"
+        let expected = [
+            (
+                ChecksumAlgorithm::Sha1,
+                "a9993e364706816aba3e25717850c26c9cd0d89d".to_string(),
+            ),
+            (
+                ChecksumAlgorithm::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad".to_string(),
+            ),
+            (ChecksumAlgorithm::Sha512, to_hex(&Sha512::digest(b"abc"))),
+            (
+                ChecksumAlgorithm::Blake3,
+                blake3::hash(b"abc").to_hex().to_string(),
+            ),
+        ];
+
+        for (algorithm, digest) in &expected {
+            assert_eq!(
+                &hash_file(&path, *algorithm).unwrap(),
+                digest,
+                "Digest mismatch for {}",
+                algorithm
+            );
+        }
+
+        // The sidecar notes the algorithm that was used
+        let manifest = manifest_path_for(&root.join("out.zip"), ChecksumAlgorithm::Sha512);
+        assert!(manifest.to_string_lossy().ends_with("out.zip.sha512"));
+        write_manifest(&[path], root, &manifest, ChecksumAlgorithm::Sha512).unwrap();
+        let content = std::fs::read_to_string(&manifest).unwrap();
+        assert_eq!(content, format!("SHA512 (abc.txt) = {}\n", expected[2].1));
+    }
+
+    #[test]
+    fn test_content_address_names_archive_after_its_digest() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+
+        let files = crate::scan_files(&crate::ScanConfig::new(&root, vec![])).unwrap();
+        let archive = temp_dir.path().join(".pack.tmp");
+        let config = crate::PackConfig::new(&root, &archive);
+        crate::pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let expected = blake3::hash(&std::fs::read(&archive).unwrap()).to_hex();
+
+        let stored = content_address(&archive, "zip").unwrap();
+        assert_eq!(
+            stored.file_name().unwrap().to_string_lossy(),
+            format!("{}.zip", expected)
+        );
+        assert_eq!(stored.parent(), archive.parent());
+        assert!(!archive.exists());
+        assert!(zip::ZipArchive::new(File::open(&stored).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_one_pass_manifest_matches_reference() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let files = crate::scan_files(&crate::ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = crate::PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        config.hash_files = Some(ChecksumAlgorithm::Blake3);
+        let summary = crate::pack_files(&files, &config, |_, _, _| {}).unwrap();
+        assert_eq!(summary.digests.len(), 2);
+
+        let one_pass = temp_dir.path().join("one_pass.blake3");
+        let reference = temp_dir.path().join("reference.blake3");
+        write_manifest_digests(
+            &summary.digests,
+            &root,
+            &one_pass,
+            ChecksumAlgorithm::Blake3,
+        )
+        .unwrap();
+        write_manifest(&files, &root, &reference, ChecksumAlgorithm::Blake3).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&one_pass).unwrap(),
+            std::fs::read_to_string(&reference).unwrap()
+        );
+
+        // Verification checks the archive against the digests, not the files
+        std::fs::write(root.join("README.md"), b"# Changed").unwrap();
+        crate::verify_archive_digests(&files, &config, &summary.digests, |_, _, _| {}).unwrap();
+        assert!(crate::verify_archive(&files, &config, |_, _, _| {}).is_err());
+    }
+}
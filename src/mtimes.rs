@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::ZipArchive;
+
+use crate::progress::{json_string, parse_json_string};
+
+/// Name of the entry recording each file's modification time to the
+/// nanosecond, see [`PackConfig::embed_mtimes`](crate::PackConfig::embed_mtimes).
+pub const MTIMES_NAME: &str = "SRCPACK_MTIMES.json";
+
+/// Nanoseconds since the epoch of a file's modification time, `None` when the
+/// platform doesn't report it or it predates the epoch.
+pub(crate) fn mtime_nanos(metadata: &Metadata) -> Option<u64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(since_epoch.as_nanos()).ok()
+}
+
+/// Renders the [`MTIMES_NAME`] entry, one file per line:
+///
+/// ```text
+/// {
+/// "README.md":1700000000123456789,
+/// "src/main.rs":1700000042000000000
+/// }
+/// ```
+pub(crate) fn mtimes_json(mtimes: &[(String, u64)]) -> String {
+    let lines: Vec<String> = mtimes
+        .iter()
+        .map(|(name, nanos)| format!("{}:{}", json_string(name), nanos))
+        .collect();
+    format!("{{\n{}\n}}\n", lines.join(",\n"))
+}
+
+/// Reads the [`MTIMES_NAME`] entry of an archive, stored at its root or
+/// under a prefix. Returns `None` when the archive has none.
+pub(crate) fn read_mtimes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<Option<HashMap<String, SystemTime>>> {
+    let suffix = format!("/{}", MTIMES_NAME);
+    let Some(name) = archive
+        .file_names()
+        .find(|name| *name == MTIMES_NAME || name.ends_with(&suffix))
+        .map(str::to_string)
+    else {
+        return Ok(None);
+    };
+
+    let mut json = String::new();
+    archive.by_name(&name)?.read_to_string(&mut json)?;
+    let mut mtimes = HashMap::new();
+    for line in json.lines().filter(|line| line.starts_with('"')) {
+        let (entry_name, rest) = parse_json_string(line)
+            .with_context(|| format!("Malformed line in {}: {:?}", name, line))?;
+        let nanos: u64 = rest
+            .trim_start_matches(':')
+            .trim_end_matches(',')
+            .parse()
+            .with_context(|| format!("Malformed line in {}: {:?}", name, line))?;
+        mtimes.insert(entry_name, UNIX_EPOCH + Duration::from_nanos(nanos));
+    }
+    Ok(Some(mtimes))
+}
+
+/// Sets the modification time of an extracted file.
+pub(crate) fn set_mtime(path: &Path, mtime: SystemTime) -> Result<()> {
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(mtime))
+        .with_context(|| format!("Failed to set the modification time of {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{extract_archive, pack_files, scan_files, ExtractConfig, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sub_second_mtime_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("odd \"name\".txt"), b"quoted").unwrap();
+        let mtime = UNIX_EPOCH + Duration::new(1_700_000_001, 123_456_789);
+        set_mtime(&root.join("src/main.rs"), mtime).unwrap();
+        set_mtime(&root.join("odd \"name\".txt"), mtime).unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let archive_path = temp_dir.path().join("out.zip");
+        let mut config = PackConfig::new(&root, &archive_path);
+        config.prefix = Some("project".to_string());
+        config.embed_mtimes = true;
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        // Without the restore option, files get the time of extraction
+        let dest = temp_dir.path().join("plain");
+        extract_archive(&ExtractConfig::new(&archive_path, &dest)).unwrap();
+        let restored = std::fs::metadata(dest.join("project/src/main.rs")).unwrap();
+        assert_ne!(restored.modified().unwrap(), mtime);
+
+        let dest = temp_dir.path().join("restored");
+        let mut extract = ExtractConfig::new(&archive_path, &dest);
+        extract.strip_components = 1;
+        extract.restore_mtimes = true;
+        extract_archive(&extract).unwrap();
+        for name in ["src/main.rs", "odd \"name\".txt"] {
+            let restored = std::fs::metadata(dest.join(name)).unwrap();
+            assert_eq!(restored.modified().unwrap(), mtime, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+use std::fs::Metadata;
+use std::path::Path;
+use zip::write::FullFileOptions;
+
+/// Header id of the Info-ZIP "new unix" extra field, holding uid and gid.
+const UNIX_OWNER_FIELD: u16 = 0x7875;
+
+/// Records the owner of a file in the entry's extra field (no-op outside unix).
+pub(crate) fn add_owner_field(
+    options: &mut FullFileOptions<'_>,
+    metadata: &Metadata,
+) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Version 1, then the size and little-endian value of the uid and gid
+        let mut data = vec![1, 4];
+        data.extend_from_slice(&metadata.uid().to_le_bytes());
+        data.push(4);
+        data.extend_from_slice(&metadata.gid().to_le_bytes());
+        options.add_extra_data(UNIX_OWNER_FIELD, data, false)?;
+    }
+    #[cfg(not(unix))]
+    let _ = (options, metadata);
+    Ok(())
+}
+
+/// Reads the `(uid, gid)` recorded in an entry's extra data, if any.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::unix_owner;
+/// use std::fs::File;
+///
+/// let mut archive = zip::ZipArchive::new(File::open("backup.zip").unwrap()).unwrap();
+/// let entry = archive.by_index(0).unwrap();
+/// if let Some((uid, gid)) = entry.extra_data().and_then(unix_owner) {
+///     println!("{}: {}:{}", entry.name(), uid, gid);
+/// }
+/// ```
+pub fn unix_owner(extra_data: &[u8]) -> Option<(u32, u32)> {
+    let mut rest = extra_data;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let data = rest.get(4..4 + len)?;
+        if id == UNIX_OWNER_FIELD {
+            return parse_owner_field(data);
+        }
+        rest = &rest[4 + len..];
+    }
+    None
+}
+
+fn parse_owner_field(data: &[u8]) -> Option<(u32, u32)> {
+    let (&version, rest) = data.split_first()?;
+    if version != 1 {
+        return None;
+    }
+    let (uid, rest) = read_id(rest)?;
+    let (gid, _) = read_id(rest)?;
+    Some((uid, gid))
+}
+
+/// Reads one size-prefixed little-endian id (1 to 4 bytes are accepted).
+fn read_id(data: &[u8]) -> Option<(u32, &[u8])> {
+    let (&size, rest) = data.split_first()?;
+    let size = size as usize;
+    if !(1..=4).contains(&size) || rest.len() < size {
+        return None;
+    }
+    let mut bytes = [0u8; 4];
+    bytes[..size].copy_from_slice(&rest[..size]);
+    Some((u32::from_le_bytes(bytes), &rest[size..]))
+}
+
+/// Gives an extracted path the owner recorded in its extra data.
+///
+/// Returns false when the archive recorded no owner (or outside unix), and
+/// the error when changing it failed (typically without root privileges).
+pub(crate) fn restore_owner(path: &Path, extra_data: Option<&[u8]>) -> std::io::Result<bool> {
+    match extra_data.and_then(unix_owner) {
+        #[cfg(unix)]
+        Some((uid, gid)) => {
+            std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+            Ok(true)
+        }
+        _ => {
+            let _ = path;
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{extract_archive, pack_files, scan_files, ExtractConfig, PackConfig, ScanConfig};
+    use std::fs::File;
+    use std::os::unix::fs::MetadataExt;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_ownership_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        let metadata = std::fs::metadata(root.join("main.rs")).unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let output_zip_path = temp_dir.path().join("owned.zip");
+        let mut config = PackConfig::new(&root, &output_zip_path);
+        config.preserve_ownership = true;
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        let entry = archive.by_name("main.rs").unwrap();
+        assert_eq!(
+            entry.extra_data().and_then(unix_owner),
+            Some((metadata.uid(), metadata.gid()))
+        );
+        drop(entry);
+
+        // Restoring our own ownership works with or without privileges
+        let dest = temp_dir.path().join("out");
+        let mut extract_config = ExtractConfig::new(&output_zip_path, &dest);
+        extract_config.preserve_ownership = true;
+        extract_archive(&extract_config).unwrap();
+        let extracted = std::fs::metadata(dest.join("main.rs")).unwrap();
+        assert_eq!(
+            (extracted.uid(), extracted.gid()),
+            (metadata.uid(), metadata.gid())
+        );
+
+        // Without the option, no owner is recorded
+        let plain_zip_path = temp_dir.path().join("plain.zip");
+        pack_files(
+            &files,
+            &PackConfig::new(&root, &plain_zip_path),
+            |_, _, _| {},
+        )
+        .unwrap();
+        let mut archive = ZipArchive::new(File::open(&plain_zip_path).unwrap()).unwrap();
+        let entry = archive.by_name("main.rs").unwrap();
+        assert_eq!(entry.extra_data().and_then(unix_owner), None);
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use zip::write::FullFileOptions;
+use zip::ZipWriter;
+
+use crate::formats::{tar_header, TarWriter, TeeReader};
+use crate::manifest::HashingReader;
+use crate::throttle::{Throttled, TokenBucket};
+use crate::{write_generated_entry, ChecksumAlgorithm};
+
+/// Extension of the entry listing the parts of a split file, see
+/// [`PackConfig::max_entry_size`](crate::PackConfig::max_entry_size).
+pub const PARTS_EXTENSION: &str = "parts";
+
+/// Fewest digits of a part index, see [`part_name`].
+const PART_INDEX_WIDTH: usize = 4;
+
+/// Name of the `index`-th of `count` parts of a split entry:
+/// `data/big.bin.part0000`. The index is zero-padded to the same width in
+/// every part, so sorting the names keeps the parts in order.
+pub fn part_name(entry_name: &str, index: u64, count: u64) -> String {
+    let width = count
+        .saturating_sub(1)
+        .to_string()
+        .len()
+        .max(PART_INDEX_WIDTH);
+    format!("{}.part{:0width$}", entry_name, index)
+}
+
+/// A file written as several entries of at most `part_size` bytes.
+pub(crate) struct SplitFile<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) entry_name: &'a str,
+    pub(crate) mode: u32,
+    pub(crate) part_size: u64,
+}
+
+/// Writes a file as numbered part entries, followed by a `.parts` entry
+/// listing them in order:
+///
+/// ```text
+/// # srcpack parts: data/big.bin, 25000000 bytes
+/// data/big.bin.part0000
+/// data/big.bin.part0001
+/// data/big.bin.part0002
+/// ```
+///
+/// Concatenating the parts in that order gives the file back. The file is
+/// read once; returns its size, and the digest of the whole file when
+/// `algorithm` is set.
+pub(crate) fn write_parts<W: Write + Seek>(
+    mut zip: Option<&mut ZipWriter<W>>,
+    mut tar: Option<&mut TarWriter>,
+    options: &FullFileOptions<'static>,
+    file: SplitFile<'_>,
+    throttle: Option<&mut TokenBucket>,
+    algorithm: Option<ChecksumAlgorithm>,
+) -> Result<(u64, Option<String>)> {
+    let f = File::open(file.path).with_context(|| format!("Failed to open {:?}", file.path))?;
+    let metadata = f.metadata()?;
+    let size = metadata.len();
+    let mut reader = HashingReader::new(Throttled::new(f.take(size), throttle), algorithm);
+
+    let mut listing = format!("# srcpack parts: {}, {} bytes\n", file.entry_name, size);
+    let part_count = size.div_ceil(file.part_size.max(1)).max(1);
+    for index in 0..part_count {
+        let name = part_name(file.entry_name, index, part_count);
+        let mut part = (&mut reader).take(file.part_size);
+        if let Some(zip) = zip.as_deref_mut() {
+            zip.start_file(name.as_str(), options.clone())?;
+        }
+        match (zip.as_deref_mut(), tar.as_deref_mut()) {
+            (zip, Some(tar)) => {
+                let mut header = tar_header(&metadata, file.mode);
+                header.set_size(file.part_size.min(size - index * file.part_size));
+                match zip {
+                    Some(zip) => {
+                        let tee = TeeReader {
+                            inner: &mut part,
+                            sink: zip,
+                        };
+                        tar.append_data(&mut header, &name, tee)?
+                    }
+                    None => tar.append_data(&mut header, &name, &mut part)?,
+                }
+            }
+            (Some(zip), None) => {
+                std::io::copy(&mut part, zip)
+                    .with_context(|| format!("Failed to pack {:?}", file.path))?;
+            }
+            (None, None) => {}
+        }
+        listing.push_str(&name);
+        listing.push('\n');
+    }
+
+    let listing_name = format!("{}.{}", file.entry_name, PARTS_EXTENSION);
+    write_generated_entry(zip, tar, options, &listing_name, &listing)?;
+    Ok((size, reader.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_large_file_is_split_into_parts() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("data")).unwrap();
+        let data: Vec<u8> = (0..250_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(root.join("data/big.bin"), &data).unwrap();
+        std::fs::write(root.join("small.txt"), b"small").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        // 13 parts: past 10, the names still sort in order
+        config.max_entry_size = Some(20_000);
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(names.len(), 15);
+        assert_eq!(names[0], "data/big.bin.part0000");
+        assert_eq!(names[12], "data/big.bin.part0012");
+        assert_eq!(names[13..], ["data/big.bin.parts", "small.txt"]);
+
+        let mut listing = String::new();
+        let mut parts = archive.by_name("data/big.bin.parts").unwrap();
+        parts.read_to_string(&mut listing).unwrap();
+        drop(parts);
+        let listed: Vec<&str> = listing
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(listed, names[..13]);
+        let mut joined = Vec::new();
+        for name in listed {
+            let mut part = archive.by_name(name).unwrap();
+            assert!(part.size() <= 20_000);
+            part.read_to_end(&mut joined).unwrap();
+        }
+        assert_eq!(joined, data);
+        drop(archive);
+
+        // A zero limit is no limit, rather than one empty part per byte read
+        config.max_entry_size = Some(0);
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, vec!["data/big.bin", "small.txt"]);
+    }
+
+    #[test]
+    fn test_part_names_are_zero_padded() {
+        assert_eq!(part_name("big.bin", 7, 12), "big.bin.part0007");
+        assert_eq!(part_name("big.bin", 42, 12_000), "big.bin.part00042");
+    }
+}
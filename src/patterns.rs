@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+
+/// User patterns mixing excludes and re-includes (`!pattern`), applied in order.
+///
+/// The last pattern matching a path, or one of the directories above it,
+/// decides: an exclude leaves the path out, a re-include keeps it. So
+/// `build/` then `!build/keep.txt` packs `build/keep.txt` and nothing else
+/// from `build/`. Unlike include-only patterns, which go through the override
+/// matcher as whitelists, a re-include doesn't override ignore files.
+#[derive(Clone)]
+pub(crate) struct LayeredPatterns {
+    root: PathBuf,
+    rules: Vec<Rule>,
+}
+
+#[derive(Clone)]
+struct Rule {
+    matcher: Gitignore,
+    pattern: String,
+    include: bool,
+}
+
+/// The pattern deciding a path, see [`LayeredPatterns::decide`].
+pub(crate) struct Decision<'a> {
+    index: usize,
+    /// The pattern as given, with its `!` for a re-include.
+    pub pattern: &'a str,
+    pub include: bool,
+    /// The path or parent directory the pattern matched.
+    pub matched: PathBuf,
+}
+
+impl LayeredPatterns {
+    /// Returns `None` unless the patterns mix excludes and re-includes.
+    pub(crate) fn new(root: &Path, patterns: &[String]) -> Result<Option<Self>> {
+        let includes = patterns.iter().filter(|p| p.starts_with('!')).count();
+        if includes == 0 || includes == patterns.len() {
+            return Ok(None);
+        }
+
+        let mut rules = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            let (glob, include) = match pattern.strip_prefix('!') {
+                Some(glob) => (glob, true),
+                None => (pattern.as_str(), false),
+            };
+            let kind = if include { "include" } else { "exclude" };
+            // One matcher per pattern, to know which came last
+            let mut builder = GitignoreBuilder::new(root);
+            builder
+                .add_line(None, glob)
+                .with_context(|| format!("Invalid {} pattern: {:?}", kind, pattern))?;
+            rules.push(Rule {
+                matcher: builder.build()?,
+                pattern: pattern.clone(),
+                include,
+            });
+        }
+        Ok(Some(Self {
+            root: root.to_path_buf(),
+            rules,
+        }))
+    }
+
+    /// Finds the last pattern matching `path` or one of its parent directories
+    /// below the root.
+    pub(crate) fn decide(&self, path: &Path, is_dir: bool) -> Option<Decision<'_>> {
+        for (index, rule) in self.rules.iter().enumerate().rev() {
+            let mut level_is_dir = is_dir;
+            for level in path.ancestors() {
+                if level == self.root || level.as_os_str().is_empty() {
+                    break;
+                }
+                if rule.matcher.matched(level, level_is_dir).is_ignore() {
+                    return Some(Decision {
+                        index,
+                        pattern: &rule.pattern,
+                        include: rule.include,
+                        matched: level.to_path_buf(),
+                    });
+                }
+                level_is_dir = true;
+            }
+        }
+        None
+    }
+
+    /// Returns true if the patterns leave `path` out.
+    pub(crate) fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        self.decide(path, is_dir).is_some_and(|d| !d.include)
+    }
+
+    /// Returns true if an excluded directory can be skipped as a whole, i.e.
+    /// no re-include comes after the pattern excluding it.
+    pub(crate) fn prunes(&self, dir: &Path) -> bool {
+        match self.decide(dir, true) {
+            Some(decision) if !decision.include => {
+                !self.rules[decision.index + 1..].iter().any(|r| r.include)
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A curated set of cache and generated files of an ecosystem, excluded from
+/// the scan with [`ScanConfig::presets`](crate::ScanConfig::presets).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Node,
+    Python,
+    Rust,
+    Java,
+    Macos,
+    Windows,
+}
+
+impl Preset {
+    /// Every preset, in the order they are listed in help texts.
+    pub const ALL: [Preset; 6] = [
+        Preset::Node,
+        Preset::Python,
+        Preset::Rust,
+        Preset::Java,
+        Preset::Macos,
+        Preset::Windows,
+    ];
+
+    /// The gitignore patterns the preset excludes.
+    pub fn patterns(&self) -> &'static [&'static str] {
+        match self {
+            Preset::Node => &[
+                "node_modules/",
+                ".npm/",
+                ".pnpm-store/",
+                ".yarn/cache/",
+                ".next/",
+                ".nuxt/",
+                ".parcel-cache/",
+                ".turbo/",
+                ".eslintcache",
+                "npm-debug.log*",
+                "yarn-error.log*",
+            ],
+            Preset::Python => &[
+                "__pycache__/",
+                "*.py[cod]",
+                ".mypy_cache/",
+                ".pytest_cache/",
+                ".ruff_cache/",
+                ".tox/",
+                ".nox/",
+                ".venv/",
+                "*.egg-info/",
+            ],
+            Preset::Rust => &["target/", "*.rs.bk"],
+            Preset::Java => &["*.class", ".gradle/"],
+            Preset::Macos => &[
+                ".DS_Store",
+                "._*",
+                ".AppleDouble/",
+                ".Spotlight-V100/",
+                ".Trashes/",
+            ],
+            Preset::Windows => &["Thumbs.db", "ehthumbs.db", "desktop.ini", "$RECYCLE.BIN/"],
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Preset::Node => "node",
+            Preset::Python => "python",
+            Preset::Rust => "rust",
+            Preset::Java => "java",
+            Preset::Macos => "macos",
+            Preset::Windows => "windows",
+        })
+    }
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.to_string() == s.to_ascii_lowercase())
+            .with_context(|| {
+                format!(
+                    "Unknown preset {:?} (expected node, python, rust, java, macos or windows)",
+                    s
+                )
+            })
+    }
+}
+
+/// Builds a matcher excluding the patterns of every preset, or `None` when
+/// there are none.
+pub(crate) fn preset_matcher(root: &Path, presets: &[Preset]) -> Result<Option<Gitignore>> {
+    if presets.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in presets.iter().flat_map(|preset| preset.patterns()) {
+        builder.add_line(None, pattern)?;
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{relative_entry_name, scan_files, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_python_preset_drops_bytecode() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("pkg/__pycache__")).unwrap();
+        std::fs::write(root.join("pkg/__init__.py"), b"").unwrap();
+        std::fs::write(root.join("pkg/__pycache__/__init__.cpython-312.pyc"), b"").unwrap();
+        std::fs::write(root.join("pkg/old.pyc"), b"").unwrap();
+        std::fs::write(root.join(".DS_Store"), b"").unwrap();
+
+        let mut config = ScanConfig::new(root, vec![]);
+        config.presets = vec!["python".parse().unwrap(), Preset::Macos];
+        let names: Vec<String> = scan_files(&config)
+            .unwrap()
+            .iter()
+            .map(|path| relative_entry_name(path, root))
+            .collect();
+        assert_eq!(names, vec!["pkg/__init__.py"]);
+
+        assert!("cobol".parse::<Preset>().is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt::{self, Write};
+use std::path::Path;
+
+/// The stage of a run a progress callback reports on.
+///
+/// Callbacks taking a `Phase` (like [`scan_files_with_progress`],
+/// [`pack_files_with_progress`] and [`verify_archive`]) tag each update with
+/// it, so one consumer can render all stages of a run in a single bar.
+///
+/// [`scan_files_with_progress`]: crate::scan_files_with_progress
+/// [`pack_files_with_progress`]: crate::pack_files_with_progress
+/// [`verify_archive`]: crate::verify_archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Phase {
+    /// Walking the directory; the count is the number of files found.
+    Scanning,
+    /// Writing the archive; the count is the number of bytes packed.
+    Packing,
+    /// Reading the archive back; the count is the number of bytes verified.
+    Verifying,
+    /// Computing checksums; the count is the number of bytes hashed.
+    Hashing,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Scanning => "Scanning",
+            Phase::Packing => "Packing",
+            Phase::Verifying => "Verifying",
+            Phase::Hashing => "Hashing",
+        })
+    }
+}
+
+/// Formats a progress update as a single-line JSON object, for wrapping
+/// srcpack in other programs:
+///
+/// ```text
+/// {"path":"src/main.rs","size":1024,"total":4096}
+/// ```
+///
+/// `path` is the path as given (usually relative to the root), `size` the size
+/// of the packed file and `total` the number of bytes packed so far.
+///
+/// # Example
+///
+/// ```
+/// use srcpack::ndjson_progress_line;
+/// use std::path::Path;
+///
+/// let line = ndjson_progress_line(Path::new("src/main.rs"), 12, 19);
+/// assert_eq!(line, r#"{"path":"src/main.rs","size":12,"total":19}"#);
+/// ```
+pub fn ndjson_progress_line(path: &Path, size: u64, total: u64) -> String {
+    format!(
+        r#"{{"path":{},"size":{},"total":{}}}"#,
+        json_string(&path.to_string_lossy().replace('\\', "/")),
+        size,
+        total
+    )
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Reads back a string literal written by [`json_string`] at the start of
+/// `s`, returning it and the rest of the input.
+pub(crate) fn parse_json_string(s: &str) -> Option<(String, &str)> {
+    let body = s.strip_prefix('"')?;
+    let mut out = String::with_capacity(body.len());
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &body[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                '"' => out.push('"'),
+                '\\' => out.push('\\'),
+                '/' => out.push('/'),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = (&mut chars).take(4).map(|(_, c)| c).collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ndjson_progress_lines_parse() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        let mut lines = Vec::new();
+        pack_files(&files, &config, |path, size, total| {
+            let relative = crate::relative_path(path, &config.root_path).unwrap();
+            lines.push(ndjson_progress_line(&relative, size, total));
+        })
+        .unwrap();
+
+        assert_eq!(lines.len(), 2);
+        let mut total = 0;
+        for line in &lines {
+            assert!(!line.contains('\n'));
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let path = value["path"].as_str().unwrap();
+            assert!(path == "src/main.rs" || path == "README.md");
+            total += value["size"].as_u64().unwrap();
+            assert_eq!(value["total"].as_u64().unwrap(), total);
+        }
+
+        // Special characters are escaped
+        let line = ndjson_progress_line(Path::new("say \"hi\"\t\u{1}.txt"), 1, 1);
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["path"], "say \"hi\"\t\u{1}.txt");
+    }
+}
@@ -0,0 +1,227 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+use zip::CompressionMethod;
+
+use crate::progress::json_string;
+use crate::run_log::utc_timestamp;
+use crate::{PackConfig, ScanConfig};
+
+/// Name of the entry written first with [`PackConfig::meta`].
+pub const META_NAME: &str = "SRCPACK_META.txt";
+
+/// Name of the entry written with [`PackConfig::provenance`].
+pub const PROVENANCE_NAME: &str = "SRCPACK_PROVENANCE.json";
+
+/// How an archive was produced, embedded as a [`PROVENANCE_NAME`] entry for
+/// reproducibility audits.
+///
+/// The entry records the srcpack version, the arguments, the compression of
+/// the [`PackConfig`] and the patterns of the [`ScanConfig`]:
+///
+/// ```text
+/// {"tool":"srcpack","version":"0.2.4","args":["-x","*.mp4"],"compression":"deflated",
+///  "level":null,"excludes":["*.mp4"],"includes":[],"timestamp":"2026-10-16T08:30:00Z"}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// Command-line arguments, without the program name.
+    pub args: Vec<String>,
+    /// [`ScanConfig::exclude_patterns`] of the scan.
+    pub excludes: Vec<String>,
+    /// [`ScanConfig::force_include`] of the scan.
+    pub includes: Vec<String>,
+    /// When the archive was made. `None` leaves it out, so that packing the
+    /// same tree twice gives the same entry.
+    pub timestamp: Option<SystemTime>,
+}
+
+impl Provenance {
+    /// Records the patterns of a scan, with no arguments and no timestamp.
+    pub fn new(scan: &ScanConfig) -> Self {
+        Self {
+            args: Vec::new(),
+            excludes: scan.exclude_patterns.clone(),
+            includes: scan.force_include.clone(),
+            timestamp: None,
+        }
+    }
+
+    /// Renders the entry for an archive packed with `config`.
+    pub(crate) fn to_json(&self, config: &PackConfig) -> String {
+        let list = |items: &[String]| {
+            let items: Vec<String> = items.iter().map(|s| json_string(s)).collect();
+            format!("[{}]", items.join(","))
+        };
+        let level = match config.compression_level {
+            Some(level) => level.to_string(),
+            None => String::from("null"),
+        };
+        let timestamp = match self.timestamp {
+            Some(time) => format!(",\"timestamp\":\"{}\"", utc_timestamp(time)),
+            None => String::new(),
+        };
+        format!(
+            "{{\"tool\":\"srcpack\",\"version\":\"{}\",\"args\":{},\"compression\":\"{}\",\
+             \"level\":{},\"excludes\":{},\"includes\":{}{}}}\n",
+            env!("CARGO_PKG_VERSION"),
+            list(&self.args),
+            compression_name(config.compression_method),
+            level,
+            list(&self.excludes),
+            list(&self.includes),
+            timestamp
+        )
+    }
+}
+
+/// The name of a compression method, as accepted by `parse_compression`.
+pub(crate) fn compression_name(method: CompressionMethod) -> String {
+    match method {
+        CompressionMethod::Stored => String::from("stored"),
+        CompressionMethod::Deflated => String::from("deflated"),
+        CompressionMethod::Zstd => String::from("zstd"),
+        other => format!("{:?}", other).to_ascii_lowercase(),
+    }
+}
+
+/// Where and when an archive was made, written as its first entry
+/// ([`META_NAME`]) so it is found at the start of the central directory:
+///
+/// ```text
+/// srcpack: 0.2.4
+/// created: 2026-10-16T08:30:00Z
+/// host: buildbox
+/// root: /home/me/project
+/// ```
+///
+/// Fields left `None` are omitted, so the default holds only stable content.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArchiveMeta {
+    pub created: Option<SystemTime>,
+    pub host: Option<String>,
+    /// The source root path.
+    pub root: Option<PathBuf>,
+}
+
+impl ArchiveMeta {
+    /// Records the current time, the host name and the given source root.
+    pub fn capture(root: impl Into<PathBuf>) -> Self {
+        Self {
+            created: Some(SystemTime::now()),
+            host: host_name(),
+            root: Some(root.into()),
+        }
+    }
+
+    pub(crate) fn to_text(&self) -> String {
+        let mut text = format!("srcpack: {}\n", env!("CARGO_PKG_VERSION"));
+        if let Some(created) = self.created {
+            text.push_str(&format!("created: {}\n", utc_timestamp(created)));
+        }
+        if let Some(host) = &self.host {
+            text.push_str(&format!("host: {}\n", host));
+        }
+        if let Some(root) = &self.root {
+            text.push_str(&format!("root: {}\n", root.display()));
+        }
+        text
+    }
+}
+
+/// The name of this machine, if it can be found without a system call.
+fn host_name() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files};
+    use std::fs::File;
+    use std::io::Read;
+    use std::time::Duration;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_provenance_entry() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("demo.mp4"), b"video").unwrap();
+
+        let mut scan_config = ScanConfig::new(&root, vec![String::from("*.mp4")]);
+        scan_config.force_include = vec![String::from("config/*.example")];
+        let files = scan_files(&scan_config).unwrap();
+
+        let read_provenance = |config: &PackConfig| {
+            pack_files(&files, config, |_, _, _| {}).unwrap();
+            let mut archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+            let mut content = String::new();
+            archive
+                .by_name(PROVENANCE_NAME)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            serde_json::from_str::<serde_json::Value>(&content).unwrap()
+        };
+
+        let mut config = PackConfig::new(&root, temp_dir.path().join("audited.zip"));
+        config.compression_level = Some(9);
+        let mut provenance = Provenance::new(&scan_config);
+        provenance.args = vec![String::from("-x"), String::from("*.mp4")];
+        config.provenance = Some(provenance.clone());
+        let json = read_provenance(&config);
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["excludes"], serde_json::json!(["*.mp4"]));
+        assert_eq!(json["includes"], serde_json::json!(["config/*.example"]));
+        assert_eq!(json["args"], serde_json::json!(["-x", "*.mp4"]));
+        assert_eq!(json["compression"], "deflated");
+        assert_eq!(json["level"], 9);
+        // Left out unless given
+        assert!(json.get("timestamp").is_none());
+
+        provenance.timestamp = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_210_096));
+        config.provenance = Some(provenance);
+        let json = read_provenance(&config);
+        assert_eq!(json["timestamp"], "2024-02-29T12:34:56Z");
+    }
+
+    #[test]
+    fn test_meta_is_the_first_entry() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+
+        let read_meta = |config: &PackConfig| {
+            pack_files(&files, config, |_, _, _| {}).unwrap();
+            let mut archive = ZipArchive::new(File::open(&config.output_path).unwrap()).unwrap();
+            let mut entry = archive.by_index(0).unwrap();
+            assert_eq!(entry.name(), META_NAME);
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            content
+        };
+
+        let mut config = PackConfig::new(&root, temp_dir.path().join("traced.zip"));
+        let mut meta = ArchiveMeta::capture(&root);
+        meta.created = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_210_096));
+        config.meta = Some(meta);
+        let content = read_meta(&config);
+        assert!(content.contains("created: 2024-02-29T12:34:56Z\n"));
+        assert!(content.contains(&format!("root: {}\n", root.display())));
+
+        // Without the run-specific fields, only stable content is left
+        config.meta = Some(ArchiveMeta::default());
+        let content = read_meta(&config);
+        assert_eq!(content, format!("srcpack: {}\n", env!("CARGO_PKG_VERSION")));
+    }
+}
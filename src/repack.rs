@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::Zip64Policy;
+
+/// Rewrites an existing zip archive with another compression method and level.
+///
+/// Entry names, unix permissions and modification times are kept; the source
+/// tree is not needed. Returns the number of entries written.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::repack_archive;
+/// use std::path::Path;
+/// use zip::CompressionMethod;
+///
+/// repack_archive(
+///     Path::new("stored.zip"),
+///     Path::new("deflated.zip"),
+///     CompressionMethod::Deflated,
+///     Some(9),
+/// )
+/// .unwrap();
+/// ```
+pub fn repack_archive(
+    input_path: &Path,
+    output_path: &Path,
+    method: CompressionMethod,
+    level: Option<i64>,
+) -> Result<usize> {
+    let input = File::open(input_path)
+        .with_context(|| format!("Failed to open archive: {:?}", input_path))?;
+    let mut archive = ZipArchive::new(BufReader::new(input))
+        .with_context(|| format!("Failed to read archive: {:?}", input_path))?;
+
+    // Creating the output would truncate the archive being read
+    if let (Ok(a), Ok(b)) = (input_path.canonicalize(), output_path.canonicalize()) {
+        if a == b {
+            anyhow::bail!("Cannot repack {:?} onto itself", input_path);
+        }
+    }
+    let output = File::create(output_path)
+        .with_context(|| format!("Failed to create output file: {:?}", output_path))?;
+    let mut zip = ZipWriter::new(BufWriter::with_capacity(1024 * 1024, output));
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry.name().to_string();
+
+        let mut options = SimpleFileOptions::default()
+            .compression_method(method)
+            .compression_level(level)
+            .large_file(Zip64Policy::Auto.large_file(entry.size()));
+        if let Some(time) = entry.last_modified() {
+            options = options.last_modified_time(time);
+        }
+        if let Some(mode) = entry.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+
+        if entry.is_dir() {
+            zip.add_directory(name, options)?;
+        } else if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            zip.add_symlink(name, target, options)?;
+        } else {
+            zip.start_file(name.clone(), options)
+                .with_context(|| format!("Failed to recompress {:?}", name))?;
+            std::io::copy(&mut entry, &mut zip)
+                .with_context(|| format!("Failed to read entry {:?}", name))?;
+        }
+    }
+
+    zip.finish()?.flush()?;
+    Ok(archive.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_repack_stored_to_deflated() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        let source = "fn main() { println!(\"hello\"); }\n".repeat(100);
+        std::fs::write(root.join("src/main.rs"), &source).unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let stored_zip = temp_dir.path().join("stored.zip");
+        let mut config = PackConfig::new(&root, &stored_zip);
+        config.compression_method = CompressionMethod::Stored;
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let deflated_zip = temp_dir.path().join("deflated.zip");
+        let count = repack_archive(
+            &stored_zip,
+            &deflated_zip,
+            CompressionMethod::Deflated,
+            Some(9),
+        )
+        .unwrap();
+        assert_eq!(count, 2);
+
+        let mut before = ZipArchive::new(File::open(&stored_zip).unwrap()).unwrap();
+        let mut after = ZipArchive::new(File::open(&deflated_zip).unwrap()).unwrap();
+        assert_eq!(after.len(), 2);
+        for index in 0..before.len() {
+            let old = before.by_index(index).unwrap();
+            let (name, mode, time) = (old.name().to_string(), old.unix_mode(), old.last_modified());
+            assert_eq!(old.compression(), CompressionMethod::Stored);
+            drop(old);
+
+            let mut new = after.by_name(&name).unwrap();
+            assert_eq!(new.compression(), CompressionMethod::Deflated);
+            assert_eq!(new.unix_mode(), mode);
+            assert_eq!(new.last_modified(), time);
+            let mut content = Vec::new();
+            new.read_to_end(&mut content).unwrap();
+            let expected = std::fs::read(root.join(&name)).unwrap();
+            assert_eq!(content, expected);
+        }
+
+        // Repacking onto the input is refused instead of truncating it
+        assert!(
+            repack_archive(&stored_zip, &stored_zip, CompressionMethod::Deflated, None).is_err()
+        );
+        assert!(ZipArchive::new(File::open(&stored_zip).unwrap()).is_ok());
+    }
+}
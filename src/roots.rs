@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::{find_skipped, relative_entry_name, scan_files, Attachment, ScanConfig, SkippedPath};
+
+/// Extension of the files read by [`read_root_list`] when given as the path
+/// to pack.
+pub const ROOT_LIST_EXTENSION: &str = "list";
+
+/// Returns true if `path` is a file with the [`ROOT_LIST_EXTENSION`].
+pub fn is_root_list(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext == ROOT_LIST_EXTENSION)
+}
+
+/// Reads a list of directories to pack, one per line.
+///
+/// Blank lines and lines starting with `#` are skipped, and relative paths
+/// are resolved against the directory of the list. Each root is packed under
+/// its base name (see [`scan_roots`]), so two roots with the same base name
+/// are refused.
+pub fn read_root_list(list_path: &Path) -> Result<Vec<PathBuf>> {
+    let content = std::fs::read_to_string(list_path)
+        .with_context(|| format!("Failed to read root list: {:?}", list_path))?;
+    let base = list_path.parent().unwrap_or(Path::new(""));
+
+    let mut roots = Vec::new();
+    let mut names = HashSet::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let root = base.join(line).canonicalize().with_context(|| {
+            format!("Cannot access directory on line {}: {:?}", number + 1, line)
+        })?;
+        if !root.is_dir() {
+            anyhow::bail!("Not a directory on line {}: {:?}", number + 1, line);
+        }
+        let name = root.file_name().unwrap_or_default().to_os_string();
+        if !names.insert(name.clone()) {
+            anyhow::bail!(
+                "Two roots are named {:?} (line {}), their entries would collide",
+                name,
+                number + 1
+            );
+        }
+        roots.push(root);
+    }
+    if roots.is_empty() {
+        anyhow::bail!("No directories listed in {:?}", list_path);
+    }
+    Ok(roots)
+}
+
+/// Scans several roots with the settings of `config`, returning their files
+/// as attachments named `<root base name>/<relative path>`.
+///
+/// Set them as [`PackConfig::attachments`] to pack all roots into one archive.
+///
+/// [`PackConfig::attachments`]: crate::PackConfig::attachments
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files, read_root_list, scan_roots, PackConfig, ScanConfig};
+/// use std::path::Path;
+///
+/// let roots = read_root_list(Path::new("services.list")).unwrap();
+/// let mut config = PackConfig::new(".", "services.zip");
+/// config.attachments = scan_roots(&roots, &ScanConfig::new(".", vec![])).unwrap();
+/// pack_files(&[], &config, |_, _, _| {}).unwrap();
+/// ```
+pub fn scan_roots(roots: &[PathBuf], config: &ScanConfig) -> Result<Vec<Attachment>> {
+    let mut attachments = Vec::new();
+    for root in roots {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Cannot access directory: {:?}", root))?;
+        let name = root
+            .file_name()
+            .with_context(|| format!("Cannot pack {:?} under a name", root))?
+            .to_string_lossy()
+            .into_owned();
+
+        let mut root_config = config.clone();
+        root_config.root_path = root.clone();
+        for source in scan_files(&root_config)? {
+            let entry_name = format!("{}/{}", name, relative_entry_name(&source, &root));
+            attachments.push(Attachment { source, entry_name });
+        }
+    }
+    Ok(attachments)
+}
+
+/// Lists the paths [`scan_roots`] left out of each root, like [`find_skipped`]
+/// does for a single root. `attachments` is the result of [`scan_roots`], and
+/// the paths are named like the entries: `<root base name>/<relative path>`.
+pub fn find_skipped_in_roots(
+    roots: &[PathBuf],
+    config: &ScanConfig,
+    attachments: &[Attachment],
+) -> Result<Vec<SkippedPath>> {
+    let mut skipped = Vec::new();
+    for root in roots {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Cannot access directory: {:?}", root))?;
+        let name = root.file_name().unwrap_or_default().to_string_lossy();
+
+        let files: Vec<PathBuf> = attachments
+            .iter()
+            .filter(|attachment| attachment.source.starts_with(&root))
+            .map(|attachment| attachment.source.clone())
+            .collect();
+        let mut root_config = config.clone();
+        root_config.root_path = root.clone();
+        for mut path in find_skipped(&root_config, &files)? {
+            path.path = format!("{}/{}", name, path.path);
+            skipped.push(path);
+        }
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        check_lockfile, entry_names, find_case_collisions, pack_files, top_level_breakdown,
+        write_lockfile, ChecksumAlgorithm, LockDrift, PackConfig, SkipReason,
+    };
+    use std::fs::File;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_pack_root_list() {
+        let temp_dir = tempdir().unwrap();
+        let api = temp_dir.path().join("services/api");
+        let web = temp_dir.path().join("web");
+        std::fs::create_dir_all(api.join("src")).unwrap();
+        std::fs::create_dir_all(web.join("dist")).unwrap();
+        std::fs::write(api.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(web.join(".gitignore"), "dist/\n").unwrap();
+        std::fs::write(web.join("index.html"), b"<html>").unwrap();
+        std::fs::write(web.join("dist/bundle.js"), b"built").unwrap();
+
+        // One root relative to the list, one absolute
+        let list_path = temp_dir.path().join("roots.list");
+        let list = format!("# Packed together\nservices/api\n\n{}\n", web.display());
+        std::fs::write(&list_path, list).unwrap();
+        assert!(is_root_list(&list_path));
+
+        let roots = read_root_list(&list_path).unwrap();
+        assert_eq!(roots.len(), 2);
+        let output_zip_path = temp_dir.path().join("roots.zip");
+        let mut config = PackConfig::new(temp_dir.path(), &output_zip_path);
+        let scan_config = ScanConfig::new(temp_dir.path(), vec![]);
+        config.attachments = scan_roots(&roots, &scan_config).unwrap();
+        config.hash_files = Some(ChecksumAlgorithm::Sha256);
+        let mut expected = entry_names(&[], &config);
+        let summary = pack_files(&[], &config, |_, _, _| {}).unwrap();
+        // Every listed file gets a digest, for --manifest, --verify and --report
+        let mut hashed: Vec<&str> = summary
+            .digests
+            .iter()
+            .map(|d| d.entry_name.as_str())
+            .collect();
+        hashed.sort();
+        assert_eq!(
+            hashed,
+            vec!["api/src/main.rs", "web/.gitignore", "web/index.html"]
+        );
+
+        // What was skipped is looked up in the roots, not around the list
+        let skipped = find_skipped_in_roots(&roots, &scan_config, &config.attachments).unwrap();
+        let skipped: Vec<(&str, SkipReason)> = skipped
+            .iter()
+            .map(|s| (s.path.as_str(), s.reason))
+            .collect();
+        assert_eq!(skipped, vec![("web/dist/", SkipReason::Gitignore)]);
+
+        let archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+        names.sort();
+        expected.sort();
+        assert_eq!(names, expected);
+        assert_eq!(
+            names,
+            vec!["api/src/main.rs", "web/.gitignore", "web/index.html"]
+        );
+
+        // Roots sharing a base name would collide
+        std::fs::create_dir_all(temp_dir.path().join("api")).unwrap();
+        std::fs::write(&list_path, "services/api\napi\n").unwrap();
+        assert!(read_root_list(&list_path).is_err());
+    }
+
+    #[test]
+    fn test_root_list_files_are_checked_like_scanned_ones() {
+        let temp_dir = tempdir().unwrap();
+        let api = temp_dir.path().join("api");
+        let web = temp_dir.path().join("web");
+        std::fs::create_dir_all(api.join("src")).unwrap();
+        std::fs::create_dir_all(&web).unwrap();
+        std::fs::write(api.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(api.join("src/Main.rs"), b"fn Main() {}").unwrap();
+        std::fs::write(web.join("index.html"), b"<html>").unwrap();
+        let roots = vec![api, web.clone()];
+        let attachments = scan_roots(&roots, &ScanConfig::new(temp_dir.path(), vec![])).unwrap();
+
+        // Grouped by root, as the dry run shows them
+        let breakdown: Vec<(String, usize, u64)> =
+            top_level_breakdown(&[], temp_dir.path(), &attachments)
+                .into_iter()
+                .map(|dir| (dir.name, dir.files, dir.bytes))
+                .collect();
+        // On case-insensitive filesystems, Main.rs replaced main.rs
+        if attachments.len() == 3 {
+            assert_eq!(
+                breakdown,
+                vec![(String::from("api"), 2, 24), (String::from("web"), 1, 6)]
+            );
+            let mut collisions = find_case_collisions(&[], temp_dir.path(), &attachments);
+            assert_eq!(collisions.len(), 1);
+            collisions[0].sort();
+            assert_eq!(collisions[0], vec!["api/src/Main.rs", "api/src/main.rs"]);
+        }
+
+        let lock_path = temp_dir.path().join("roots.lock");
+        write_lockfile(&[], temp_dir.path(), &attachments, &lock_path).unwrap();
+        let locked = std::fs::read_to_string(&lock_path).unwrap();
+        assert!(locked.contains("web/index.html "), "{}", locked);
+        assert_eq!(
+            check_lockfile(&[], temp_dir.path(), &attachments, &lock_path).unwrap(),
+            vec![]
+        );
+        std::fs::write(web.join("index.html"), b"<html></html>").unwrap();
+        assert_eq!(
+            check_lockfile(&[], temp_dir.path(), &attachments, &lock_path).unwrap(),
+            vec![LockDrift::Changed(String::from("web/index.html"))]
+        );
+    }
+}
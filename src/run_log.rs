@@ -0,0 +1,169 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A persistent, timestamped record of a run, for unattended packing.
+///
+/// Each call to [`RunLog::line`] appends one line prefixed with the UTC time,
+/// and reaches the file right away:
+///
+/// ```text
+/// 2026-10-16T08:30:00Z packed: src/main.rs (1024 bytes)
+/// ```
+///
+/// Write errors don't interrupt the run (lines are written from progress
+/// callbacks); the first one is returned by [`RunLog::finish`].
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files, scan_files, PackConfig, RunLog, ScanConfig};
+/// use std::path::Path;
+///
+/// let mut log = RunLog::create(Path::new("srcpack.log")).unwrap();
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// let config = PackConfig::new(".", "archive.zip");
+/// let summary = pack_files(&files, &config, |path, size, _| {
+///     log.line(&format!("packed: {} ({} bytes)", path.display(), size));
+/// })
+/// .unwrap();
+/// log.summary(files.len(), summary.skipped.len(), &summary.outputs);
+/// log.finish().unwrap();
+/// ```
+pub struct RunLog {
+    out: LineWriter<File>,
+    path: PathBuf,
+    error: Option<std::io::Error>,
+}
+
+impl RunLog {
+    /// Opens the log file, appending to it if it exists.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {:?}", path))?;
+        Ok(Self {
+            out: LineWriter::new(file),
+            path: path.to_path_buf(),
+            error: None,
+        })
+    }
+
+    /// Appends a timestamped line.
+    pub fn line(&mut self, message: &str) {
+        if self.error.is_some() {
+            return;
+        }
+        let line = format!("{} {}\n", utc_timestamp(SystemTime::now()), message);
+        if let Err(err) = self.out.write_all(line.as_bytes()) {
+            self.error = Some(err);
+        }
+    }
+
+    /// Appends the final summary line of a run.
+    pub fn summary(&mut self, packed: usize, skipped: usize, outputs: &[PathBuf]) {
+        let outputs: Vec<String> = outputs.iter().map(|p| p.display().to_string()).collect();
+        self.line(&format!(
+            "done: {} files packed, {} skipped -> {}",
+            packed,
+            skipped,
+            outputs.join(", ")
+        ));
+    }
+
+    /// Flushes the log, returning the first error met while writing it.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err).with_context(|| format!("Failed to write log file: {:?}", self.path));
+        }
+        self.out
+            .flush()
+            .with_context(|| format!("Failed to write log file: {:?}", self.path))
+    }
+}
+
+/// Formats a time as `YYYY-MM-DDTHH:MM:SSZ`.
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, second_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days, Howard Hinnant's algorithm
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day % 3600 / 60,
+        second_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, relative_entry_name, scan_files, PackConfig, ScanConfig};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_log_records_files_and_summary() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let log_path = temp_dir.path().join("srcpack.log");
+        let mut log = RunLog::create(&log_path).unwrap();
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let config = PackConfig::new(&root, temp_dir.path().join("project.zip"));
+        let summary = pack_files(&files, &config, |path, size, _| {
+            log.line(&format!(
+                "packed: {} ({} bytes)",
+                relative_entry_name(path, &root),
+                size
+            ));
+        })
+        .unwrap();
+        log.summary(files.len(), summary.skipped.len(), &summary.outputs);
+        log.finish().unwrap();
+
+        let content = std::fs::read_to_string(&log_path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines
+            .iter()
+            .any(|l| l.ends_with(" packed: src/main.rs (12 bytes)")));
+        assert!(lines
+            .iter()
+            .any(|l| l.ends_with(" packed: README.md (7 bytes)")));
+        assert!(lines[2].contains(" done: 2 files packed, 0 skipped -> "));
+        assert!(lines[2].ends_with("project.zip"));
+        // Every line starts with its timestamp
+        assert!(lines
+            .iter()
+            .all(|l| l.as_bytes()[10] == b'T' && l.as_bytes()[19] == b'Z'));
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(utc_timestamp(time), "2024-02-29T12:34:56Z");
+    }
+}
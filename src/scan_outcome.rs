@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::skip_report::SkipLog;
+use crate::{walk_files, ScanConfig, SkippedPath};
+
+/// The bucket name used for files without an extension, see [`ExtensionStats`].
+pub const NO_EXTENSION: &str = "<none>";
+
+/// File count and size of one file extension, see [`ScanOutcome::extensions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionStats {
+    /// The extension without its dot (`rs`), or [`NO_EXTENSION`].
+    pub extension: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Everything a scan finds out, see [`scan_detailed`].
+#[derive(Debug, Clone, Default)]
+pub struct ScanOutcome {
+    /// The files to pack, as returned by [`scan_files`](crate::scan_files).
+    pub files: Vec<PathBuf>,
+    /// The paths under the root left out, with the reason (see
+    /// [`find_skipped`](crate::find_skipped)).
+    pub skipped: Vec<SkippedPath>,
+    /// The files grouped by extension, sorted by size, largest first.
+    pub extensions: Vec<ExtensionStats>,
+    /// Size of all the files, in bytes.
+    pub total_size: u64,
+    /// Errors the walk went past (an unreadable directory, an invalid ignore
+    /// file), which [`scan_files`](crate::scan_files) prints as warnings.
+    pub walk_errors: Vec<String>,
+}
+
+/// Scans the root like [`scan_files`](crate::scan_files), returning along with
+/// the files what was skipped and why, per-extension statistics, the total
+/// size and the walk errors, instead of printing the errors.
+///
+/// Everything comes from a single walk: the skipped paths are recorded as the
+/// walk rejects them, and the sizes are taken from the walked entries.
+/// Ignored directories are not entered, and a directory that can't be read is
+/// listed as skipped as well as in the walk errors. Unlike
+/// [`find_skipped`](crate::find_skipped), a skipped directory holding
+/// force-included files is not listed.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{scan_detailed, ScanConfig};
+///
+/// let outcome = scan_detailed(&ScanConfig::new(".", vec![])).unwrap();
+/// println!("{} files, {} bytes", outcome.files.len(), outcome.total_size);
+/// for stats in &outcome.extensions {
+///     println!("{:<8} {:>6} files {:>10} bytes", stats.extension, stats.files, stats.bytes);
+/// }
+/// for skipped in &outcome.skipped {
+///     println!("skipped {} ({})", skipped.path, skipped.reason);
+/// }
+/// ```
+pub fn scan_detailed(config: &ScanConfig) -> Result<ScanOutcome> {
+    let root = config
+        .root_path
+        .canonicalize()
+        .with_context(|| format!("Cannot access directory: {:?}", config.root_path))?;
+    let skip_log = Arc::new(SkipLog::new(config, &root)?);
+
+    let mut walk_errors = Vec::new();
+    let mut sizes = HashMap::new();
+    let files = walk_files(
+        config,
+        |_, _, _| {},
+        |err| walk_errors.push(err.to_string()),
+        |path, metadata| {
+            sizes.insert(path.to_path_buf(), metadata.len());
+        },
+        Some(skip_log.clone()),
+    )?;
+    let (skipped, ignore_errors) = skip_log.finish();
+    walk_errors.extend(ignore_errors.iter().map(ToString::to_string));
+
+    let mut extensions: Vec<ExtensionStats> = Vec::new();
+    let mut index_by_extension: HashMap<String, usize> = HashMap::new();
+    let mut total_size = 0;
+    for path in &files {
+        // Files added after the walk (force-included, kept) are stat'ed here
+        let size = match sizes.get(path) {
+            Some(&size) => size,
+            None => match std::fs::metadata(path) {
+                Ok(metadata) if metadata.is_file() => metadata.len(),
+                _ => continue,
+            },
+        };
+
+        let extension = path
+            .extension()
+            .map_or_else(|| NO_EXTENSION.to_string(), |e| e.to_string_lossy().into());
+        let index = *index_by_extension
+            .entry(extension.clone())
+            .or_insert_with(|| {
+                extensions.push(ExtensionStats {
+                    extension,
+                    files: 0,
+                    bytes: 0,
+                });
+                extensions.len() - 1
+            });
+        extensions[index].files += 1;
+        extensions[index].bytes += size;
+        total_size += size;
+    }
+    extensions.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+
+    Ok(ScanOutcome {
+        files,
+        skipped,
+        extensions,
+        total_size,
+        walk_errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_files, SkipReason};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_scan_detailed_outcome() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        // An unclosed character class: the rule is reported, not applied
+        std::fs::write(root.join(".ignore"), "broken[\n").unwrap();
+        std::fs::write(root.join("src/main.rs"), vec![b'a'; 300]).unwrap();
+        std::fs::write(root.join("src/lib.rs"), vec![b'b'; 200]).unwrap();
+        std::fs::write(root.join("Makefile"), vec![b'c'; 50]).unwrap();
+        std::fs::write(root.join("target/app"), b"binary").unwrap();
+        std::fs::write(root.join("demo.mp4"), b"video").unwrap();
+
+        #[cfg(unix)]
+        {
+            // Neither a dangling symlink nor one out of the root fails the scan
+            std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+            std::os::unix::fs::symlink(temp_dir.path(), root.join("outside")).unwrap();
+        }
+
+        let config = ScanConfig::new(&root, vec![String::from("*.mp4")]);
+        let outcome = scan_detailed(&config).unwrap();
+
+        let mut files = outcome.files.clone();
+        files.sort();
+        let mut expected = scan_files(&config).unwrap();
+        expected.sort();
+        assert_eq!(files, expected);
+        assert_eq!(files.len(), 5);
+
+        let skipped: Vec<(&str, SkipReason)> = outcome
+            .skipped
+            .iter()
+            .map(|s| (s.path.as_str(), s.reason))
+            .filter(|(_, reason)| *reason != SkipReason::Symlink)
+            .collect();
+        #[cfg(unix)]
+        assert_eq!(outcome.skipped.len(), skipped.len() + 2);
+        assert_eq!(
+            skipped,
+            vec![
+                ("demo.mp4", SkipReason::UserExclude),
+                ("target/", SkipReason::Gitignore)
+            ]
+        );
+
+        let stats = |extension: &str, files: usize, bytes: u64| ExtensionStats {
+            extension: extension.to_string(),
+            files,
+            bytes,
+        };
+        // Dotfiles like .gitignore have no extension
+        assert_eq!(
+            outcome.extensions,
+            vec![stats("rs", 2, 500), stats(NO_EXTENSION, 3, 66)]
+        );
+        assert_eq!(outcome.total_size, 566);
+
+        assert_eq!(outcome.walk_errors.len(), 1, "{:?}", outcome.walk_errors);
+        assert!(outcome.walk_errors[0].contains("broken["));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_detailed_unreadable_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("locked")).unwrap();
+        std::fs::write(root.join("locked/secret.txt"), b"hidden").unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        let locked = root.join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let still_readable = std::fs::read_dir(&locked).is_ok();
+
+        let outcome = scan_detailed(&ScanConfig::new(&root, vec![]));
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // Running as root, permissions don't keep anything out
+        if still_readable {
+            return;
+        }
+
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.files.len(), 1);
+        assert_eq!(outcome.total_size, 12);
+        assert_eq!(outcome.skipped.len(), 1, "{:?}", outcome.skipped);
+        assert_eq!(outcome.skipped[0].path, "locked/");
+        assert_eq!(outcome.skipped[0].reason, SkipReason::Unreadable);
+        assert_eq!(outcome.walk_errors.len(), 1, "{:?}", outcome.walk_errors);
+    }
+
+    #[test]
+    fn test_scan_detailed_matches_scan_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src/.cache")).unwrap();
+        std::fs::create_dir_all(root.join("build/keep")).unwrap();
+        std::fs::write(root.join(".gitignore"), "build/\n*.log\n!keep.log\n").unwrap();
+        std::fs::write(root.join(".env"), b"KEY=1").unwrap();
+        std::fs::write(root.join("keep.log"), b"kept").unwrap();
+        std::fs::write(root.join("debug.log"), b"log").unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("src/.cache/state"), b"state").unwrap();
+        std::fs::write(root.join("build/app"), b"binary").unwrap();
+        std::fs::write(root.join("build/keep/notes.txt"), b"notes").unwrap();
+
+        let mut configs = Vec::new();
+        configs.push(ScanConfig::new(&root, vec![]));
+        let mut config = ScanConfig::new(&root, vec![String::from("!*.log")]);
+        config.hidden = crate::HiddenPolicy::Excluded;
+        configs.push(config);
+        let mut config = ScanConfig::new(&root, vec![String::from("src/"), String::from("!*.rs")]);
+        config.hidden = crate::HiddenPolicy::DirsExcludedFilesIncluded;
+        configs.push(config);
+        let mut config = ScanConfig::new(&root, vec![]);
+        config.force_include = vec![String::from("build/keep/*.txt")];
+        config.parent_ignores = false;
+        configs.push(config);
+
+        for config in &configs {
+            let outcome = scan_detailed(config).unwrap();
+            let mut files = outcome.files.clone();
+            files.sort();
+            let mut expected = scan_files(config).unwrap();
+            expected.sort();
+            assert_eq!(files, expected, "{:?}", config.exclude_patterns);
+
+            let skipped: Vec<&str> = outcome.skipped.iter().map(|s| s.path.as_str()).collect();
+            let expected_skipped = crate::find_skipped(config, &expected).unwrap();
+            let expected_skipped: Vec<&str> =
+                expected_skipped.iter().map(|s| s.path.as_str()).collect();
+            // A skipped directory holding force-included files is left out
+            if config.force_include.is_empty() {
+                assert_eq!(skipped, expected_skipped, "{:?}", config.exclude_patterns);
+            }
+        }
+    }
+}
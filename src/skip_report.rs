@@ -0,0 +1,533 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::explain::Explainer;
+use crate::gitattributes::ExportIgnore;
+use crate::progress::json_string;
+use ignore::Match;
+
+use crate::{relative_entry_name, HiddenPolicy, ScanConfig, Verdict};
+
+/// Name of the report entry written with [`PackConfig::embed_skip_report`].
+///
+/// [`PackConfig::embed_skip_report`]: crate::PackConfig::embed_skip_report
+pub const SKIP_REPORT_NAME: &str = "SRCPACK_SKIPPED.json";
+
+/// Why a path under the root is not in the archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// A rule of an ignore file (`.gitignore`, `.ignore`, ...).
+    Gitignore,
+    /// A user exclude pattern, or include patterns it doesn't match.
+    UserExclude,
+    /// A pattern of one of the [`ScanConfig::presets`].
+    Preset,
+    /// A hidden file or directory, with [`ScanConfig::hidden`].
+    Hidden,
+    /// An `export-ignore` attribute, with [`ScanConfig::export_ignore`].
+    ExportIgnore,
+    /// A directory holding the [`ScanConfig::ignore_marker`] file.
+    IgnoreMarker,
+    /// Too large to be packed (e.g. a sparse file over the limit).
+    Size,
+    /// A symlink the walk doesn't follow: broken, or to a directory.
+    Symlink,
+    /// A directory that couldn't be read, which the scan only warns about.
+    Unreadable,
+    /// Left out after the scan (e.g. not tracked by git, over a file limit).
+    Filtered,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Gitignore => "gitignore",
+            SkipReason::UserExclude => "user-exclude",
+            SkipReason::Preset => "preset",
+            SkipReason::Hidden => "hidden",
+            SkipReason::ExportIgnore => "export-ignore",
+            SkipReason::IgnoreMarker => "ignore-marker",
+            SkipReason::Size => "size",
+            SkipReason::Symlink => "symlink",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::Filtered => "filtered",
+        })
+    }
+}
+
+/// A path left out of the archive, see [`find_skipped`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedPath {
+    /// Relative path, ending with `/` for a directory skipped as a whole.
+    pub path: String,
+    pub reason: SkipReason,
+    /// The rule responsible, in words.
+    pub detail: String,
+}
+
+/// Lists the paths under the root that are not in `files`, with the reason.
+///
+/// `files` is the result of [`scan_files`](crate::scan_files) for `config`,
+/// possibly filtered further. A directory left out as a whole is listed once,
+/// without its contents. A directory that can't be read is listed as
+/// [`SkipReason::Unreadable`], with the error, rather than failing the call.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{find_skipped, scan_files, ScanConfig};
+///
+/// let config = ScanConfig::new(".", vec![String::from("*.mp4")]);
+/// let files = scan_files(&config).unwrap();
+/// for skipped in find_skipped(&config, &files).unwrap() {
+///     println!("{} ({}): {}", skipped.path, skipped.reason, skipped.detail);
+/// }
+/// ```
+pub fn find_skipped(config: &ScanConfig, files: &[PathBuf]) -> Result<Vec<SkippedPath>> {
+    let root = config
+        .root_path
+        .canonicalize()
+        .with_context(|| format!("Cannot access directory: {:?}", config.root_path))?;
+
+    let packed: HashSet<&Path> = files.iter().map(PathBuf::as_path).collect();
+    // Directories with something packed below them
+    let mut reached = HashSet::new();
+    for path in files {
+        for dir in path.ancestors().skip(1) {
+            if !reached.insert(dir) || dir == root {
+                break;
+            }
+        }
+    }
+
+    let mut classifier = Classifier::new(config, &root)?;
+    let mut skipped = Vec::new();
+    let mut pending = vec![root.clone()];
+    while let Some(dir) = pending.pop() {
+        let mut entries: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .collect(),
+            Err(err) => {
+                skipped.push(SkippedPath {
+                    path: format!("{}/", relative_entry_name(&dir, &root)),
+                    reason: SkipReason::Unreadable,
+                    detail: format!("could not be read: {}", err),
+                });
+                continue;
+            }
+        };
+        entries.sort();
+
+        for path in entries {
+            // Symlinks are not followed, like when scanning
+            let is_dir = path.symlink_metadata().is_ok_and(|m| m.is_dir());
+            if packed.contains(path.as_path()) || (is_dir && reached.contains(path.as_path())) {
+                if is_dir {
+                    pending.push(path);
+                }
+                continue;
+            }
+
+            match classifier.classify(&path, is_dir) {
+                // Nothing excludes the directory itself, look at its contents
+                (SkipReason::Filtered, _) if is_dir => pending.push(path),
+                (reason, detail) => {
+                    let mut name = relative_entry_name(&path, &root);
+                    if is_dir {
+                        name.push('/');
+                    }
+                    skipped.push(SkippedPath {
+                        path: name,
+                        reason,
+                        detail,
+                    });
+                }
+            }
+        }
+    }
+
+    skipped.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(skipped)
+}
+
+/// The paths a walk leaves out, recorded as it goes rather than by listing
+/// the directories again afterwards, see [`scan_detailed`].
+///
+/// [`scan_detailed`]: crate::scan_detailed
+pub(crate) struct SkipLog {
+    root: PathBuf,
+    classifier: Mutex<Classifier>,
+    skipped: Mutex<Vec<SkippedPath>>,
+}
+
+impl SkipLog {
+    /// `root` is the canonical root of the scan.
+    pub(crate) fn new(config: &ScanConfig, root: &Path) -> Result<Self> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            classifier: Mutex::new(Classifier::new(config, root)?),
+            skipped: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Applies the rules the walker checks on its own (user patterns, ignore
+    /// files, hidden entries with [`HiddenPolicy::Excluded`]) to an entry,
+    /// recording it if they exclude it.
+    pub(crate) fn walker_rejects(&self, path: &Path, is_dir: bool) -> bool {
+        let mut classifier = self.classifier.lock().unwrap();
+        let excluded = match classifier.explainer.walker_rules(path, is_dir) {
+            Match::Ignore(verdict) => classifier.excluded_by(verdict),
+            Match::None if classifier.hidden == HiddenPolicy::Excluded && is_hidden(path) => {
+                Some((SkipReason::Hidden, classifier.hidden_detail()))
+            }
+            _ => None,
+        };
+        drop(classifier);
+
+        let Some((reason, detail)) = excluded else {
+            return false;
+        };
+        self.record(path, is_dir, reason, detail);
+        true
+    }
+
+    /// Records an entry rejected by the filters of the walk, finding out which.
+    pub(crate) fn reject(&self, path: &Path, is_dir: bool) {
+        let (reason, detail) = self.classifier.lock().unwrap().classify(path, is_dir);
+        self.record(path, is_dir, reason, detail);
+    }
+
+    /// Records a path the walk reached but doesn't pack: a symlink it doesn't
+    /// follow, or a file filtered out (e.g. by its modification time).
+    pub(crate) fn not_packed(&self, path: &Path) {
+        match symlink_detail(path) {
+            Some(detail) => self.record(path, false, SkipReason::Symlink, detail),
+            None => self.record(
+                path,
+                false,
+                SkipReason::Filtered,
+                String::from("left out after the scan"),
+            ),
+        }
+    }
+
+    /// Records the directory of a walk error that couldn't be read.
+    pub(crate) fn unreadable(&self, err: &ignore::Error) {
+        if let (Some(path), Some(io_err)) = (error_path(err), err.io_error()) {
+            let detail = format!("could not be read: {}", io_err);
+            self.record(path, true, SkipReason::Unreadable, detail);
+        }
+    }
+
+    pub(crate) fn record(&self, path: &Path, is_dir: bool, reason: SkipReason, detail: String) {
+        let mut name = relative_entry_name(path, &self.root);
+        if is_dir {
+            name.push('/');
+        }
+        self.skipped.lock().unwrap().push(SkippedPath {
+            path: name,
+            reason,
+            detail,
+        });
+    }
+
+    /// Forgets the recorded paths that are, or hold, files added after the
+    /// walk (force-included or kept files).
+    pub(crate) fn forget(&self, added: &[PathBuf]) {
+        let names: Vec<String> = added
+            .iter()
+            .map(|path| relative_entry_name(path, &self.root))
+            .collect();
+        self.skipped.lock().unwrap().retain(|skipped| {
+            !names.iter().any(|name| match skipped.path.ends_with('/') {
+                true => name.starts_with(&skipped.path),
+                false => *name == skipped.path,
+            })
+        });
+    }
+
+    /// Returns the recorded paths, sorted, and the errors of the ignore files
+    /// read along the way.
+    pub(crate) fn finish(&self) -> (Vec<SkippedPath>, Vec<ignore::Error>) {
+        let mut skipped = std::mem::take(&mut *self.skipped.lock().unwrap());
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        let errors = self.classifier.lock().unwrap().explainer.take_errors();
+        (skipped, errors)
+    }
+}
+
+/// The path a walk error is about, if any.
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+struct Classifier {
+    root: PathBuf,
+    hidden: HiddenPolicy,
+    ignore_marker: Option<String>,
+    explainer: Explainer,
+    export_ignore: Option<ExportIgnore>,
+}
+
+impl Classifier {
+    fn new(config: &ScanConfig, root: &Path) -> Result<Self> {
+        Ok(Self {
+            root: root.to_path_buf(),
+            hidden: config.hidden,
+            ignore_marker: config.ignore_marker.clone(),
+            explainer: Explainer::new(config, root)?,
+            export_ignore: config.export_ignore.then(|| ExportIgnore::new(root)),
+        })
+    }
+
+    /// Finds the rule excluding a path, in the order the scan applies them.
+    /// The path is not resolved: it may be a symlink out of the root, or a
+    /// dangling one.
+    fn classify(&mut self, path: &Path, is_dir: bool) -> (SkipReason, String) {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let verdict = self.explainer.explain(relative, is_dir);
+        if let Some(excluded) = self.excluded_by(verdict) {
+            return excluded;
+        }
+
+        let hidden_excluded = match self.hidden {
+            HiddenPolicy::Included => false,
+            HiddenPolicy::Excluded => true,
+            HiddenPolicy::DirsExcludedFilesIncluded => is_dir,
+        };
+        if is_hidden(path) && hidden_excluded {
+            return (SkipReason::Hidden, self.hidden_detail());
+        }
+        if let Some(export_ignore) = &self.export_ignore {
+            if export_ignore.is_ignored(path, is_dir) {
+                return (
+                    SkipReason::ExportIgnore,
+                    String::from("excluded by an export-ignore attribute"),
+                );
+            }
+        }
+        if let Some(marker) = self.ignore_marker.as_deref() {
+            if is_dir && path.join(marker).exists() {
+                return (
+                    SkipReason::IgnoreMarker,
+                    format!("excluded by the marker file {}", marker),
+                );
+            }
+        }
+
+        if let Some(detail) = symlink_detail(path) {
+            return (SkipReason::Symlink, detail);
+        }
+        (
+            SkipReason::Filtered,
+            String::from("left out after the scan"),
+        )
+    }
+
+    /// The reason and detail of a verdict excluding a path, `None` for the
+    /// other verdicts.
+    fn excluded_by(&self, mut verdict: Verdict) -> Option<(SkipReason, String)> {
+        let reason = match &mut verdict {
+            Verdict::ExcludedByIgnoreFile { file, .. } => {
+                // The report travels with the archive, keep local paths out of it
+                if let Ok(relative) = file.strip_prefix(&self.root) {
+                    *file = relative.to_path_buf();
+                }
+                SkipReason::Gitignore
+            }
+            Verdict::ExcludedByPattern { .. } | Verdict::NotIncluded => SkipReason::UserExclude,
+            Verdict::ExcludedByPreset { .. } => SkipReason::Preset,
+            _ => return None,
+        };
+        Some((reason, verdict.to_string()))
+    }
+
+    fn hidden_detail(&self) -> String {
+        format!("excluded as hidden (hidden policy `{}`)", self.hidden)
+    }
+}
+
+/// Describes a symlink the walk leaves out, `None` for other paths. Like the
+/// ignore files, link targets are local paths and are kept out of the report.
+fn symlink_detail(path: &Path) -> Option<String> {
+    path.symlink_metadata()
+        .ok()
+        .filter(|metadata| metadata.file_type().is_symlink())?;
+    Some(String::from(match std::fs::metadata(path) {
+        Err(_) => "a broken symlink",
+        Ok(metadata) if metadata.is_dir() => "a symlink to a directory, not followed",
+        Ok(_) => "a symlink, left out after the scan",
+    }))
+}
+
+/// Renders the skipped paths as the JSON report embedded in archives.
+///
+/// ```text
+/// {"skipped":[{"path":"target/","reason":"gitignore","detail":"excluded by rule ..."}]}
+/// ```
+pub(crate) fn skip_report_json(skipped: &[SkippedPath]) -> String {
+    let entries: Vec<String> = skipped
+        .iter()
+        .map(|s| {
+            format!(
+                r#"{{"path":{},"reason":"{}","detail":{}}}"#,
+                json_string(&s.path),
+                s.reason,
+                json_string(&s.detail)
+            )
+        })
+        .collect();
+    format!("{{\"skipped\":[\n{}\n]}}\n", entries.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, Preset};
+    use std::fs::File;
+    use std::io::Read;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_embedded_skip_report() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("src/debug.log"), b"noise").unwrap();
+        std::fs::write(root.join("target/debug/app"), b"binary").unwrap();
+        std::fs::write(root.join("demo.mp4"), b"video").unwrap();
+        std::fs::write(root.join(".DS_Store"), b"finder").unwrap();
+
+        let mut scan_config = ScanConfig::new(&root, vec![String::from("*.mp4")]);
+        scan_config.presets = vec![Preset::Macos];
+        let files = scan_files(&scan_config).unwrap();
+        let skipped = find_skipped(&scan_config, &files).unwrap();
+
+        let output_zip_path = temp_dir.path().join("audited.zip");
+        let mut config = PackConfig::new(&root, &output_zip_path);
+        config.embed_skip_report = Some(skipped);
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        let mut report = String::new();
+        archive
+            .by_name(SKIP_REPORT_NAME)
+            .unwrap()
+            .read_to_string(&mut report)
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+        let entries: Vec<(&str, &str)> = report["skipped"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e["path"].as_str().unwrap(), e["reason"].as_str().unwrap()))
+            .collect();
+
+        // The ignored directory is listed once, without its contents
+        assert_eq!(
+            entries,
+            vec![
+                (".DS_Store", "preset"),
+                ("demo.mp4", "user-exclude"),
+                ("src/debug.log", "gitignore"),
+                ("target/", "gitignore"),
+            ]
+        );
+        let detail = report["skipped"][2]["detail"].as_str().unwrap();
+        assert!(detail.contains("*.log"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_reported_not_fatal() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        let outside = temp_dir.path().join("etc");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("passwd"), b"root").unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join(".gitignore"), "ignored-link\n").unwrap();
+        std::os::unix::fs::symlink(root.join("missing"), root.join("dangling")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("etc-link")).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("ignored-link")).unwrap();
+
+        // Also when the root is given through a symlink
+        let linked_root = temp_dir.path().join("linked");
+        std::os::unix::fs::symlink(&root, &linked_root).unwrap();
+        let config = ScanConfig::new(&linked_root, vec![]);
+        let files = scan_files(&config).unwrap();
+        let skipped = find_skipped(&config, &files).unwrap();
+        let reasons: Vec<(&str, SkipReason)> = skipped
+            .iter()
+            .map(|s| (s.path.as_str(), s.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("dangling", SkipReason::Symlink),
+                ("etc-link", SkipReason::Symlink),
+                ("ignored-link", SkipReason::Gitignore),
+            ]
+        );
+        assert!(
+            skipped[0].detail.contains("broken"),
+            "{}",
+            skipped[0].detail
+        );
+        assert!(
+            skipped[1].detail.contains("not followed"),
+            "{}",
+            skipped[1].detail
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_directory_is_reported_not_fatal() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("locked")).unwrap();
+        std::fs::write(root.join("locked/secret.txt"), b"hidden").unwrap();
+        std::fs::write(root.join("main.rs"), b"fn main() {}").unwrap();
+        let locked = root.join("locked");
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let still_readable = std::fs::read_dir(&locked).is_ok();
+
+        let config = ScanConfig::new(&root, vec![]);
+        let files = scan_files(&config).unwrap();
+        let skipped = find_skipped(&config, &files);
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        // Running as root, permissions don't keep anything out
+        if still_readable {
+            return;
+        }
+
+        let skipped = skipped.unwrap();
+        assert_eq!(skipped.len(), 1, "{:?}", skipped);
+        assert_eq!(skipped[0].path, "locked/");
+        assert_eq!(skipped[0].reason, SkipReason::Unreadable);
+        assert!(skipped[0].detail.contains("could not be read"));
+    }
+}
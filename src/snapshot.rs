@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::relative_path;
+
+/// A hardlinked copy of the scanned files, taken before packing.
+///
+/// The snapshot lives in a hidden temporary directory inside the root (so it
+/// is on the same filesystem) and is removed when dropped.
+#[derive(Debug)]
+pub struct Snapshot {
+    dir: TempDir,
+    files: Vec<PathBuf>,
+}
+
+impl Snapshot {
+    /// The root of the snapshot, to be used as the pack root.
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// The snapshotted files, in the order of the original list.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+/// Creates a hardlink-based snapshot of the scanned files.
+///
+/// Packing from the snapshot insulates the archive against files being
+/// replaced or deleted while it is written: editors, compilers and `git`
+/// replace files by writing a new one and renaming it over the old name, which
+/// leaves the hardlink pointing at the original content. Writes made in place
+/// to an existing file are still visible, since a hardlink shares its data.
+///
+/// Directories in the list are recreated with their permissions.
+///
+/// # Returns
+///
+/// * `Result<Option<Snapshot>>` - `None` when hardlinks are not available
+///   (unsupported filesystem, read-only tree, files on another mount), in which
+///   case the files should be packed directly.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files, scan_files, snapshot_files, PackConfig, ScanConfig};
+/// use std::path::Path;
+///
+/// let root = Path::new(".").canonicalize().unwrap();
+/// let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+/// match snapshot_files(&files, &root).unwrap() {
+///     Some(snapshot) => {
+///         let config = PackConfig::new(snapshot.root(), "backup.zip");
+///         pack_files(snapshot.files(), &config, |_, _, _| {}).unwrap();
+///     }
+///     None => {
+///         let config = PackConfig::new(&root, "backup.zip");
+///         pack_files(&files, &config, |_, _, _| {}).unwrap();
+///     }
+/// }
+/// ```
+pub fn snapshot_files(files: &[PathBuf], root_path: &Path) -> Result<Option<Snapshot>> {
+    let dir = match tempfile::Builder::new()
+        .prefix(".srcpack-snapshot-")
+        .tempdir_in(root_path)
+    {
+        Ok(dir) => dir,
+        Err(_) => return Ok(None),
+    };
+
+    let mut snapshot_files = Vec::with_capacity(files.len());
+    for path in files {
+        let relative = relative_path(path, root_path)
+            .with_context(|| format!("Cannot snapshot {:?}: not under {:?}", path, root_path))?;
+        let target = dir.path().join(relative);
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create snapshot directory: {:?}", parent))?;
+        }
+
+        if path.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            std::fs::set_permissions(&target, std::fs::metadata(path)?.permissions())?;
+        } else {
+            // Link the file a symlink points to, not the (possibly relative) link itself
+            let source = match path.symlink_metadata()?.file_type().is_symlink() {
+                true => path.canonicalize()?,
+                false => path.clone(),
+            };
+            if std::fs::hard_link(&source, &target).is_err() {
+                return Ok(None);
+            }
+        }
+
+        snapshot_files.push(target);
+    }
+
+    Ok(Some(Snapshot {
+        dir,
+        files: snapshot_files,
+    }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use std::fs::File;
+    use std::io::Read;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_snapshot_is_isolated_from_later_changes() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("config.toml"), b"version = 1").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let snapshot = snapshot_files(&files, &root)
+            .unwrap()
+            .expect("Hardlinks should be supported in the temp dir");
+
+        // Modify the tree after the snapshot: replace one file, delete another
+        std::fs::remove_file(root.join("config.toml")).unwrap();
+        std::fs::write(root.join("config.toml"), b"version = 2").unwrap();
+        std::fs::remove_file(root.join("src/main.rs")).unwrap();
+
+        let output_zip_path = temp_dir.path().join("snapshot.zip");
+        let config = PackConfig::new(snapshot.root(), &output_zip_path);
+        pack_files(snapshot.files(), &config, |_, _, _| {}).expect("Packing failed");
+
+        let mut archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut content = String::new();
+        archive
+            .by_name("config.toml")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "version = 1");
+        assert!(archive.by_name("src/main.rs").is_ok());
+
+        // The snapshot directory is cleaned up
+        let snapshot_root = snapshot.root().to_path_buf();
+        drop(snapshot);
+        assert!(!snapshot_root.exists());
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::{pack_files_multi, relative_entry_name, ArchiveFormat, PackConfig, PackSummary};
+
+/// The archive suffix used for the files directly under the root.
+pub const SPLIT_ROOT_NAME: &str = "root";
+
+/// Groups files by the first component of their path relative to the root.
+///
+/// Files directly under the root are grouped under [`SPLIT_ROOT_NAME`].
+/// Groups come in the order they first appear in `files`.
+pub fn split_by_top_level(files: &[PathBuf], root_path: &Path) -> Vec<(String, Vec<PathBuf>)> {
+    let mut groups: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    let mut index_by_name: HashMap<String, usize> = HashMap::new();
+
+    for path in files {
+        let relative = relative_entry_name(path, root_path);
+        let name = match relative.split_once('/') {
+            Some((first, _)) => first.to_string(),
+            // A top-level directory entry belongs with its own contents
+            None if path.is_dir() => relative,
+            None => SPLIT_ROOT_NAME.to_string(),
+        };
+
+        let index = *index_by_name.entry(name.clone()).or_insert_with(|| {
+            groups.push((name, Vec::new()));
+            groups.len() - 1
+        });
+        groups[index].1.push(path.clone());
+    }
+
+    groups
+}
+
+/// Returns the output path of one group: `archive.zip` becomes `archive-src.zip`.
+pub fn split_output_path(path: &Path, group: &str) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("archive"));
+
+    let (stem, extension) = [".tar.gz", ".tgz", ".zip", ".sh"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext).map(|stem| (stem, *ext)))
+        .unwrap_or((&name, ""));
+
+    path.with_file_name(format!("{}-{}{}", stem, group, extension))
+}
+
+/// Packs each top-level directory of the scan into its own archive, in one pass.
+///
+/// Files are grouped with [`split_by_top_level`] and every group is written
+/// with [`pack_files_multi`] to the path given by [`split_output_path`].
+/// Entry names are unchanged (`src/main.rs` stays `src/main.rs`).
+/// Attachments are not supported, since they belong to no directory.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files_split_by_dir, scan_files, ArchiveFormat, PackConfig, ScanConfig};
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// let config = PackConfig::new(".", "archive.zip");
+/// // archive-src.zip, archive-docs.zip, archive-root.zip, ...
+/// pack_files_split_by_dir(&files, &config, &[ArchiveFormat::Zip], |_, _, _| {}).unwrap();
+/// ```
+pub fn pack_files_split_by_dir<F>(
+    files: &[PathBuf],
+    config: &PackConfig,
+    formats: &[ArchiveFormat],
+    mut on_progress: F,
+) -> Result<PackSummary>
+where
+    F: FnMut(&PathBuf, u64, u64),
+{
+    if !config.attachments.is_empty() {
+        anyhow::bail!("Attachments cannot be used when splitting archives by directory");
+    }
+
+    let groups = split_by_top_level(files, &config.root_path);
+    let mut seen = HashSet::new();
+    for (name, _) in &groups {
+        if !seen.insert(split_output_path(&config.output_path, name)) {
+            anyhow::bail!(
+                "The top-level directory {:?} clashes with the archive of the root files",
+                name
+            );
+        }
+    }
+
+    let mut summary = PackSummary::default();
+    let mut total_processed_size = 0;
+    for (name, group_files) in &groups {
+        let mut group_config = config.clone();
+        group_config.output_path = split_output_path(&config.output_path, name);
+
+        let group_summary =
+            pack_files_multi(group_files, &group_config, formats, |path, size, _| {
+                total_processed_size += size;
+                on_progress(path, size, total_processed_size);
+            })?;
+        summary.outputs.extend(group_summary.outputs);
+        summary.stored_fallbacks += group_summary.stored_fallbacks;
+        summary.skipped.extend(group_summary.skipped);
+        summary
+            .compression_log
+            .extend(group_summary.compression_log);
+        summary.digests.extend(group_summary.digests);
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_files, ScanConfig};
+    use std::fs::File;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_split_by_top_level_dir() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("frontend")).unwrap();
+        std::fs::create_dir_all(root.join("backend/src")).unwrap();
+        std::fs::write(root.join("frontend/app.js"), b"app()").unwrap();
+        std::fs::write(root.join("backend/src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("backend/Cargo.toml"), b"[package]").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let out_dir = temp_dir.path().join("out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let config = PackConfig::new(&root, out_dir.join("archive.zip"));
+        let summary =
+            pack_files_split_by_dir(&files, &config, &[ArchiveFormat::Zip], |_, _, _| {}).unwrap();
+
+        let mut outputs = summary.outputs.clone();
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            vec![
+                out_dir.join("archive-backend.zip"),
+                out_dir.join("archive-frontend.zip")
+            ]
+        );
+
+        let names = |path: &Path| {
+            let archive = ZipArchive::new(File::open(path).unwrap()).unwrap();
+            let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            names(&out_dir.join("archive-backend.zip")),
+            vec!["backend/Cargo.toml", "backend/src/main.rs"]
+        );
+        assert_eq!(
+            names(&out_dir.join("archive-frontend.zip")),
+            vec!["frontend/app.js"]
+        );
+
+        // Root-level files get their own archive
+        assert_eq!(
+            split_output_path(Path::new("dist/archive.tar.gz"), SPLIT_ROOT_NAME),
+            Path::new("dist/archive-root.tar.gz")
+        );
+    }
+}
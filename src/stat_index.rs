@@ -0,0 +1,250 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use zip::ZipArchive;
+
+const HEADER: &str = "# srcpack stat index v1";
+const ARCHIVE_PREFIX: &str = "# archive: ";
+
+/// The stat tuple recorded for each file: `(device, inode, mtime, size)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStat {
+    dev: u64,
+    ino: u64,
+    mtime_nanos: u128,
+    size: u64,
+}
+
+impl FileStat {
+    fn of(metadata: &Metadata) -> Self {
+        #[cfg(unix)]
+        let (dev, ino) = (metadata.dev(), metadata.ino());
+        #[cfg(not(unix))]
+        let (dev, ino) = (0, 0);
+
+        let mtime_nanos = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+
+        Self {
+            dev,
+            ino,
+            mtime_nanos,
+            size: metadata.len(),
+        }
+    }
+}
+
+/// The stat index of a previous run, used to skip unchanged files.
+///
+/// The sidecar is a text file with one `dev ino mtime size name` line per entry,
+/// plus the name of the archive it describes, so unchanged entries can be
+/// copied from it without recompressing.
+#[derive(Debug, Default)]
+pub struct StatIndex {
+    archive: Option<PathBuf>,
+    entries: HashMap<String, FileStat>,
+}
+
+impl StatIndex {
+    /// Loads an index written by a previous run.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open stat index: {:?}", path))?;
+        let mut lines = BufReader::new(file).lines();
+
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            anyhow::bail!("Not a srcpack stat index: {:?}", path);
+        }
+
+        let mut index = StatIndex::default();
+        for line in lines {
+            let line = line?;
+            if let Some(archive) = line.strip_prefix(ARCHIVE_PREFIX) {
+                // The archive is recorded relative to the index
+                let dir = path.parent().unwrap_or(Path::new(""));
+                index.archive = Some(dir.join(archive));
+                continue;
+            }
+
+            let mut fields = line.splitn(5, ' ');
+            let mut next = || fields.next().context("Truncated stat index line");
+            let stat = FileStat {
+                dev: next()?.parse()?,
+                ino: next()?.parse()?,
+                mtime_nanos: next()?.parse()?,
+                size: next()?.parse()?,
+            };
+            index.entries.insert(next()?.to_string(), stat);
+        }
+
+        Ok(index)
+    }
+
+    /// Returns true if the entry was recorded with the same stat tuple.
+    pub fn is_unchanged(&self, entry_name: &str, metadata: &Metadata) -> bool {
+        self.entries.get(entry_name) == Some(&FileStat::of(metadata))
+    }
+}
+
+/// Returns the default index location for an archive (e.g. `project.zip.srcpack-index`).
+pub fn stat_index_path_for(output_path: &Path) -> PathBuf {
+    let mut name = output_path.as_os_str().to_os_string();
+    name.push(".srcpack-index");
+    PathBuf::from(name)
+}
+
+/// An index from a previous run, with its archive when it still exists.
+pub(crate) struct Reuse {
+    index: StatIndex,
+    archive: Option<ZipArchive<File>>,
+}
+
+impl Reuse {
+    pub(crate) fn open(index_path: &Path, output_path: &Path) -> Result<Self> {
+        let index = StatIndex::load(index_path)?;
+        let archive = match &index.archive {
+            Some(path) if path.exists() => {
+                // Creating the new archive would truncate the one we copy from
+                if same_file(path, output_path) {
+                    anyhow::bail!(
+                        "The previous archive {:?} would be overwritten, use another output name",
+                        path
+                    );
+                }
+                Some(
+                    ZipArchive::new(File::open(path)?)
+                        .with_context(|| format!("Failed to read previous archive: {:?}", path))?,
+                )
+            }
+            _ => None,
+        };
+        Ok(Self { index, archive })
+    }
+
+    pub(crate) fn is_unchanged(&self, entry_name: &str, metadata: &Metadata) -> bool {
+        self.index.is_unchanged(entry_name, metadata)
+    }
+
+    /// The previous archive, if it was found.
+    pub(crate) fn archive(&mut self) -> Option<&mut ZipArchive<File>> {
+        self.archive.as_mut()
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Collects the stat tuples of the packed files and writes them as an index.
+#[derive(Default)]
+pub(crate) struct StatIndexWriter {
+    lines: Vec<(FileStat, String)>,
+}
+
+impl StatIndexWriter {
+    pub(crate) fn record(&mut self, entry_name: &str, metadata: &Metadata) {
+        // The index is line based; such names are simply never reused
+        if entry_name.contains('\n') {
+            return;
+        }
+        self.lines
+            .push((FileStat::of(metadata), entry_name.to_string()));
+    }
+
+    /// Writes the index next to the archive. `is_zip` tells whether later runs
+    /// can copy entries from that archive.
+    pub(crate) fn write(&self, archive_path: &Path, is_zip: bool) -> Result<()> {
+        let index_path = stat_index_path_for(archive_path);
+        let file = File::create(&index_path)
+            .with_context(|| format!("Failed to create stat index: {:?}", index_path))?;
+        let mut writer = BufWriter::new(file);
+
+        writeln!(writer, "{}", HEADER)?;
+        if let Some(name) = archive_path.file_name().filter(|_| is_zip) {
+            writeln!(writer, "{}{}", ARCHIVE_PREFIX, name.to_string_lossy())?;
+        }
+        for (stat, name) in &self.lines {
+            writeln!(
+                writer,
+                "{} {} {} {} {}",
+                stat.dev, stat.ino, stat.mtime_nanos, stat.size, name
+            )?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{pack_files, scan_files, PackConfig, ScanConfig};
+    use std::io::Read;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_reuse_index_skips_unchanged_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("stable.txt"), b"unchanged").unwrap();
+        std::fs::write(root.join("notes.txt"), b"draft").unwrap();
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+
+        // First run: full archive and its index
+        let first_zip = temp_dir.path().join("run1.zip");
+        let mut config = PackConfig::new(&root, &first_zip);
+        config.write_stat_index = true;
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+        let index_path = stat_index_path_for(&first_zip);
+        assert!(index_path.exists());
+
+        std::fs::write(root.join("notes.txt"), b"final version").unwrap();
+
+        // Second run with the previous archive: the unchanged file is copied
+        let second_zip = temp_dir.path().join("run2.zip");
+        let mut config = PackConfig::new(&root, &second_zip);
+        config.reuse_index = Some(index_path.clone());
+        pack_files(&files, &config, |_, _, _| {}).unwrap();
+
+        let mut archive = ZipArchive::new(File::open(&second_zip).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut content = String::new();
+        archive
+            .by_name("stable.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "unchanged");
+
+        // Without the previous archive, the unchanged file is skipped
+        std::fs::remove_file(&first_zip).unwrap();
+        let third_zip = temp_dir.path().join("run3.zip");
+        let mut config = PackConfig::new(&root, &third_zip);
+        config.reuse_index = Some(index_path);
+        let mut packed = Vec::new();
+        pack_files(&files, &config, |path, _, _| packed.push(path.clone())).unwrap();
+
+        let notes: Vec<PathBuf> = files
+            .iter()
+            .filter(|path| path.ends_with("notes.txt"))
+            .cloned()
+            .collect();
+        assert_eq!(packed, notes);
+        let archive = ZipArchive::new(File::open(&third_zip).unwrap()).unwrap();
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), vec!["notes.txt"]);
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use zip::result::ZipError;
+use zip::ZipWriter;
+
+use crate::{check_entry_names, pack_entries, PackConfig, PackSummary};
+
+/// Streams a zip archive of the files into any writer (stdout, a socket, a pipe).
+///
+/// The writer doesn't need to be seekable: entry sizes and checksums are
+/// written after each entry's data instead of in its header. Because nothing
+/// can be rewritten, `config.sfx`, `config.write_stat_index` and
+/// `config.max_compress_time_per_file` are not supported in this mode;
+/// `config.output_path` is ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files_to_writer, scan_files, PackConfig, ScanConfig};
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// let config = PackConfig::new(".", "");
+/// pack_files_to_writer(&files, &config, std::io::stdout().lock(), |_, _, _| {}).unwrap();
+/// ```
+pub fn pack_files_to_writer<W, F>(
+    files: &[PathBuf],
+    config: &PackConfig,
+    writer: W,
+    on_progress: F,
+) -> Result<PackSummary>
+where
+    W: Write,
+    F: FnMut(&PathBuf, u64, u64),
+{
+    if config.sfx {
+        anyhow::bail!("A self-extracting archive cannot be streamed");
+    }
+    if config.write_stat_index {
+        anyhow::bail!("A stat index cannot be written for a streamed archive");
+    }
+    let mut config = config.clone();
+    config.max_compress_time_per_file = None;
+
+    check_entry_names(files, &config)?;
+    let mut zip = ZipWriter::new_stream(BufWriter::with_capacity(1024 * 1024, writer));
+    let summary = pack_entries(files, &config, Some(&mut zip), None, on_progress)?;
+    zip.finish()?.into_inner().flush()?;
+
+    Ok(summary)
+}
+
+/// Streams a zip archive of the files into the stdin of a shell command,
+/// e.g. `aws s3 cp - s3://bucket/backup.zip`.
+///
+/// The command runs through `sh -c` (`cmd /C` on Windows). The pack fails if
+/// the command exits with an error status, or exits before reading the whole
+/// archive. See [`pack_files_to_writer`] for the options that don't apply.
+pub fn pack_files_to_command<F>(
+    files: &[PathBuf],
+    config: &PackConfig,
+    command: &str,
+    on_progress: F,
+) -> Result<PackSummary>
+where
+    F: FnMut(&PathBuf, u64, u64),
+{
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run pipe command: {:?}", command))?;
+
+    let stdin = child.stdin.take().context("Pipe command has no stdin")?;
+    // Dropping the writer closes stdin, so the command sees the end of the archive
+    let result = pack_files_to_writer(files, config, stdin, on_progress);
+    let status = child.wait()?;
+
+    match result {
+        Err(err) if is_broken_pipe(&err) => anyhow::bail!(
+            "Pipe command {:?} exited ({}) before reading the whole archive",
+            command,
+            status
+        ),
+        Err(err) => Err(err),
+        Ok(_) if !status.success() => {
+            anyhow::bail!("Pipe command {:?} failed ({})", command, status)
+        }
+        Ok(summary) => Ok(summary),
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// Returns true if the error comes from writing to a closed pipe.
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let io_err = match cause.downcast_ref::<ZipError>() {
+            Some(ZipError::Io(io_err)) => Some(io_err),
+            _ => cause.downcast_ref::<io::Error>(),
+        };
+        io_err.is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{scan_files, ScanConfig};
+    use std::fs::File;
+    use std::io::Read;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_pack_into_pipe_command() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let config = PackConfig::new(&root, "");
+        let piped_zip = temp_dir.path().join("piped.zip");
+
+        let command = format!("cat > '{}'", piped_zip.display());
+        pack_files_to_command(&files, &config, &command, |_, _, _| {}).expect("Pipe failed");
+
+        let mut archive = ZipArchive::new(File::open(&piped_zip).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut content = String::new();
+        archive
+            .by_name("src/main.rs")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "fn main() {}");
+
+        // The exit status of the command is surfaced
+        let err = pack_files_to_command(&files, &config, "cat > /dev/null; exit 3", |_, _, _| {})
+            .unwrap_err();
+        assert!(err.to_string().contains("exit status: 3"), "{}", err);
+    }
+}
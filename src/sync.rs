@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::relative_path;
+
+/// Copies the scanned files into `dest`, keeping their layout relative to the root.
+///
+/// This is packing without an archive: the destination receives an exploded,
+/// filtered copy of the tree. Permissions are copied along with the content,
+/// directory entries (see [`ScanConfig::store_dir_entries`]) are created with
+/// their permissions, and existing files are overwritten. Files outside the
+/// root are refused.
+///
+/// Returns the paths written, in the order of `files`.
+///
+/// [`ScanConfig::store_dir_entries`]: crate::ScanConfig::store_dir_entries
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{copy_tree, scan_files, ScanConfig};
+/// use std::path::Path;
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// copy_tree(&files, Path::new("../clean-copy"), Path::new(".")).unwrap();
+/// ```
+pub fn copy_tree(files: &[PathBuf], dest: &Path, root_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::with_capacity(files.len());
+
+    for path in files {
+        let relative = relative_path(path, root_path)
+            .with_context(|| format!("{:?} is outside of the root {:?}", path, root_path))?;
+        let out_path = dest.join(relative);
+
+        if path.is_dir() {
+            std::fs::create_dir_all(&out_path)
+                .with_context(|| format!("Failed to create directory: {:?}", out_path))?;
+            let permissions = std::fs::metadata(path)?.permissions();
+            std::fs::set_permissions(&out_path, permissions)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+            }
+            // Copies the permissions too
+            std::fs::copy(path, &out_path)
+                .with_context(|| format!("Failed to copy {:?} to {:?}", path, out_path))?;
+        }
+        written.push(out_path);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_files, ScanConfig};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_copy_tree_mirrors_scan() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src/bin")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(root.join("src/bin/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+        std::fs::write(root.join("debug.log"), b"noise").unwrap();
+        std::fs::write(root.join("target/app"), b"binary").unwrap();
+
+        let files = scan_files(&ScanConfig::new(&root, vec![])).unwrap();
+        let dest = temp_dir.path().join("copy");
+        copy_tree(&files, &dest, &root).unwrap();
+
+        let mut copied: Vec<String> = ignore::WalkBuilder::new(&dest)
+            .standard_filters(false)
+            .build()
+            .map(|entry| entry.unwrap().into_path())
+            .filter(|path| path.is_file())
+            .map(|path| crate::relative_entry_name(&path, &dest))
+            .collect();
+        copied.sort();
+        assert_eq!(copied, vec![".gitignore", "README.md", "src/bin/main.rs"]);
+        assert_eq!(
+            std::fs::read(dest.join("src/bin/main.rs")).unwrap(),
+            b"fn main() {}"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let script = root.join("run.sh");
+            std::fs::write(&script, b"#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+            copy_tree(&[script], &dest, &root).unwrap();
+            let mode = std::fs::metadata(dest.join("run.sh"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{Context, Result};
+use ignore::overrides::{Override, OverrideBuilder};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use tar::EntryType;
+
+use crate::long_names::fit_name;
+use crate::patterns::LayeredPatterns;
+use crate::{create_zip_writer, finish_zip_writer, override_glob, zip_file_options, PackConfig};
+
+/// Repackages a tar stream into a zip archive, without extracting it to disk.
+///
+/// Entries are filtered by name with `exclude_patterns`, in the syntax of
+/// [`ScanConfig::exclude_patterns`]; an excluded directory excludes everything
+/// below it, except what a later `!pattern` re-includes. Files are streamed into the zip, directories and symlinks are
+/// recreated, and other entry types (hard links, devices) are skipped. The
+/// archive-wide options of `config` (compression, prefix, name normalization)
+/// apply; [`PackConfig::root_path`] is not used.
+///
+/// Returns the number of entries written. Wrap the reader in a
+/// `flate2::read::GzDecoder` for `.tar.gz` input.
+///
+/// [`ScanConfig::exclude_patterns`]: crate::ScanConfig::exclude_patterns
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_from_tar, PackConfig};
+/// use std::fs::File;
+///
+/// let input = File::open("release.tar").unwrap();
+/// let config = PackConfig::new("", "release.zip");
+/// pack_from_tar(input, &config, &[String::from("*.o")]).unwrap();
+/// ```
+pub fn pack_from_tar<R: Read>(
+    reader: R,
+    config: &PackConfig,
+    exclude_patterns: &[String],
+) -> Result<usize> {
+    let layered = LayeredPatterns::new(Path::new(""), exclude_patterns)?;
+    let mut overrides = OverrideBuilder::new(".");
+    for pattern in exclude_patterns.iter().filter(|_| layered.is_none()) {
+        let (glob, is_include) = override_glob(pattern);
+        let kind = if is_include { "include" } else { "exclude" };
+        overrides
+            .add(&glob)
+            .with_context(|| format!("Invalid {} pattern: {:?}", kind, pattern))?;
+    }
+    let overrides = overrides.build()?;
+
+    let options = zip_file_options(config);
+    let mut zip = create_zip_writer(config)?;
+    let mut archive = tar::Archive::new(reader);
+    let mut written = 0;
+
+    for entry in archive.entries().context("Failed to read tar stream")? {
+        let mut entry = entry.context("Failed to read tar entry")?;
+        let Some(path) = normalized_path(&entry.path()?) else {
+            continue;
+        };
+        let entry_type = entry.header().entry_type();
+        let excluded = match &layered {
+            Some(layered) => layered.is_excluded(&path, entry_type.is_dir()),
+            None => is_excluded(&overrides, &path, entry_type.is_dir()),
+        };
+        if excluded {
+            continue;
+        }
+
+        let name = path.to_string_lossy().replace('\\', "/");
+        let name = fit_name(config.entry_name(name), config);
+        let mode = entry.header().mode().unwrap_or(0o644) & 0o7777;
+        let options = options.clone().unix_permissions(mode);
+
+        match entry_type {
+            EntryType::Directory => zip.add_directory(name, options)?,
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name()?
+                    .with_context(|| format!("Symlink without a target: {}", name))?;
+                zip.add_symlink(name, target.to_string_lossy(), options)?;
+            }
+            EntryType::Regular | EntryType::Continuous => {
+                let options = options.large_file(config.zip64.large_file(entry.size()));
+                zip.start_file(name, options)?;
+                std::io::copy(&mut entry, &mut zip)?;
+            }
+            _ => continue,
+        }
+        written += 1;
+    }
+
+    finish_zip_writer(zip, config)?;
+    Ok(written)
+}
+
+/// Returns the relative form of a tar entry path, or `None` for the root
+/// itself and for paths escaping it.
+fn normalized_path(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+/// Applies the patterns to an entry and each of its parent directories.
+fn is_excluded(overrides: &Override, path: &Path, is_dir: bool) -> bool {
+    let excluded_parent = path
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .any(|dir| overrides.matched(dir, true).is_ignore());
+    excluded_parent || overrides.matched(path, is_dir).is_ignore()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+    use zip::ZipArchive;
+
+    fn append(builder: &mut tar::Builder<Vec<u8>>, path: &str, entry_type: EntryType, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(data.len() as u64);
+        header.set_mode(if entry_type.is_dir() { 0o755 } else { 0o644 });
+        builder.append_data(&mut header, path, data).unwrap();
+    }
+
+    #[test]
+    fn test_pack_from_tar_filters_entries() {
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "./", EntryType::Directory, b"");
+        append(&mut builder, "./src/", EntryType::Directory, b"");
+        append(
+            &mut builder,
+            "./src/main.rs",
+            EntryType::Regular,
+            b"fn main() {}",
+        );
+        append(&mut builder, "./debug.log", EntryType::Regular, b"noise");
+        append(&mut builder, "./target/", EntryType::Directory, b"");
+        append(&mut builder, "./target/app", EntryType::Regular, b"binary");
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(EntryType::Symlink);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "./latest.rs", "src/main.rs")
+            .unwrap();
+        let tar_bytes = builder.into_inner().unwrap();
+
+        let temp_dir = tempdir().unwrap();
+        let output_zip_path = temp_dir.path().join("from-tar.zip");
+        let config = PackConfig::new("", &output_zip_path);
+        let patterns = ["*.log", "target"].map(String::from);
+        let written = pack_from_tar(tar_bytes.as_slice(), &config, &patterns).unwrap();
+        assert_eq!(written, 3);
+
+        let mut archive = ZipArchive::new(File::open(&output_zip_path).unwrap()).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, vec!["latest.rs", "src/", "src/main.rs"]);
+
+        let mut content = String::new();
+        archive
+            .by_name("src/main.rs")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "fn main() {}");
+        let link = archive.by_name("latest.rs").unwrap();
+        assert!(link.is_symlink());
+        drop(link);
+        assert!(archive.by_name("src/").unwrap().is_dir());
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// Token bucket capping how fast file data is read while packing.
+///
+/// Tokens are bytes, refilled at `rate` per second up to one second worth of
+/// data. The bucket starts empty, so even a short run is held to the rate.
+pub(crate) struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a bucket allowing `bytes_per_second` on average.
+    pub(crate) fn new(bytes_per_second: u64) -> Self {
+        Self {
+            rate: bytes_per_second.max(1) as f64,
+            tokens: 0.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Takes `bytes` tokens, sleeping until the bucket would have held them.
+    fn take(&mut self, bytes: usize) {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.rate);
+        self.last = now;
+
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            // The deficit is paid back by the refill of the next call
+            std::thread::sleep(Duration::from_secs_f64(-self.tokens / self.rate));
+        }
+    }
+}
+
+/// Reader adapter drawing every byte read from an optional [`TokenBucket`].
+pub(crate) struct Throttled<'a, R> {
+    inner: R,
+    bucket: Option<&'a mut TokenBucket>,
+}
+
+impl<'a, R> Throttled<'a, R> {
+    pub(crate) fn new(inner: R, bucket: Option<&'a mut TokenBucket>) -> Self {
+        Self { inner, bucket }
+    }
+}
+
+impl<R: Read> Read for Throttled<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(bucket) = self.bucket.as_deref_mut() {
+            bucket.take(read);
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::manifest::HashingReader;
+use crate::{archive_entries, FileDigest, PackConfig, Phase};
+
+/// Checks that a freshly written zip archive holds the content of every file.
+///
+/// Each entry is decompressed (which also checks its CRC) and compared byte
+/// for byte with the file it was packed from. `on_progress` is called after
+/// every file with [`Phase::Verifying`] and the number of bytes verified so far.
+///
+/// Archives written with [`PackConfig::reuse_index`] leave unchanged files
+/// out, so they can't be verified against the full file list.
+///
+/// # Example
+///
+/// ```no_run
+/// use srcpack::{pack_files, scan_files, verify_archive, PackConfig, ScanConfig};
+///
+/// let files = scan_files(&ScanConfig::new(".", vec![])).unwrap();
+/// let config = PackConfig::new(".", "archive.zip");
+/// pack_files(&files, &config, |_, _, _| {}).unwrap();
+/// verify_archive(&files, &config, |_, _, _| {}).unwrap();
+/// ```
+pub fn verify_archive<F>(files: &[PathBuf], config: &PackConfig, on_progress: F) -> Result<()>
+where
+    F: FnMut(Phase, &Path, u64),
+{
+    verify_archive_digests(files, config, &[], on_progress)
+}
+
+/// Like [`verify_archive`], checking entries against the digests computed
+/// while packing (see [`PackConfig::hash_files`]) instead of reading their
+/// files again. Entries without a digest are compared with their file.
+pub fn verify_archive_digests<F>(
+    files: &[PathBuf],
+    config: &PackConfig,
+    digests: &[FileDigest],
+    mut on_progress: F,
+) -> Result<()>
+where
+    F: FnMut(Phase, &Path, u64),
+{
+    let digests: HashMap<&str, &str> = digests
+        .iter()
+        .map(|d| (d.entry_name.as_str(), d.digest.as_str()))
+        .collect();
+    let file = File::open(&config.output_path)
+        .with_context(|| format!("Failed to open archive: {:?}", config.output_path))?;
+    let mut archive = ZipArchive::new(BufReader::new(file))
+        .with_context(|| format!("Failed to read archive: {:?}", config.output_path))?;
+
+    let mut verified = 0;
+    for (path, name) in archive_entries(files, config) {
+        if path.is_dir() {
+            continue;
+        }
+        let mut entry = archive
+            .by_name(&name)
+            .with_context(|| format!("Entry missing from archive: {}", name))?;
+
+        if let (Some(digest), Some(algorithm)) = (digests.get(name.as_str()), config.hash_files) {
+            let mut reader = HashingReader::new(&mut entry, Some(algorithm));
+            let size = std::io::copy(&mut reader, &mut std::io::sink())
+                .with_context(|| format!("Failed to verify entry: {}", name))?;
+            if reader.finish().as_deref() != Some(*digest) {
+                anyhow::bail!("Entry {} does not match the digest of {:?}", name, path);
+            }
+            verified += size;
+            on_progress(Phase::Verifying, path, verified);
+            continue;
+        }
+
+        let source = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+
+        let size = same_content(&mut entry, BufReader::new(source))
+            .with_context(|| format!("Failed to verify entry: {}", name))?
+            .with_context(|| format!("Entry {} does not match {:?}", name, path))?;
+        verified += size;
+        on_progress(Phase::Verifying, path, verified);
+    }
+
+    Ok(())
+}
+
+/// Compares two readers to the end, returning their length if they're equal.
+fn same_content(mut a: impl Read, mut b: impl Read) -> std::io::Result<Option<u64>> {
+    let mut buf_a = vec![0u8; 64 * 1024];
+    let mut buf_b = vec![0u8; 64 * 1024];
+    let mut length = 0;
+    loop {
+        let read = a.read(&mut buf_a)?;
+        if read == 0 {
+            // `b` must be exhausted too
+            return Ok((b.read(&mut buf_b[..1])? == 0).then_some(length));
+        }
+        match b.read_exact(&mut buf_b[..read]) {
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        if buf_a[..read] != buf_b[..read] {
+            return Ok(None);
+        }
+        length += read as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        manifest_path_for, pack_files, pack_files_with_progress, scan_files_with_progress,
+        write_manifest_with_progress, ChecksumAlgorithm, ScanConfig,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_phases_of_pack_and_verify() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("README.md"), b"# Hello").unwrap();
+
+        // One callback for the whole run, as a multi-phase bar would use
+        let mut events: Vec<(Phase, PathBuf, u64)> = Vec::new();
+        let mut record = |phase: Phase, path: &Path, count: u64| {
+            events.push((phase, path.to_path_buf(), count));
+        };
+
+        let files = scan_files_with_progress(&ScanConfig::new(&root, vec![]), &mut record).unwrap();
+        let config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        pack_files_with_progress(&files, &config, &mut record).unwrap();
+        verify_archive(&files, &config, &mut record).unwrap();
+        let manifest_path = manifest_path_for(&config.output_path, ChecksumAlgorithm::Sha256);
+        write_manifest_with_progress(
+            &files,
+            &root,
+            &manifest_path,
+            ChecksumAlgorithm::Sha256,
+            &mut record,
+        )
+        .unwrap();
+
+        let phases: Vec<Phase> = events.iter().map(|(phase, _, _)| *phase).collect();
+        assert_eq!(
+            phases,
+            vec![
+                Phase::Scanning,
+                Phase::Scanning,
+                Phase::Packing,
+                Phase::Packing,
+                Phase::Verifying,
+                Phase::Verifying,
+                Phase::Hashing,
+                Phase::Hashing,
+            ]
+        );
+        // Scanning counts files, the other phases count bytes
+        assert_eq!(events[1].2, 2);
+        for phase in [Phase::Packing, Phase::Verifying, Phase::Hashing] {
+            let last = events.iter().rev().find(|(p, _, _)| *p == phase).unwrap();
+            assert_eq!(last.2, 19);
+        }
+
+        // A file changed after packing fails verification
+        std::fs::write(root.join("README.md"), b"# Hello, world").unwrap();
+        let err = verify_archive(&files, &config, |_, _, _| {}).unwrap_err();
+        assert!(err.to_string().contains("does not match"));
+    }
+
+    #[test]
+    fn test_source_verification_sees_changed_files() {
+        let temp_dir = tempdir().unwrap();
+        let root = temp_dir.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("data.txt"), b"original").unwrap();
+
+        let files =
+            scan_files_with_progress(&ScanConfig::new(&root, vec![]), |_, _, _| {}).unwrap();
+        let mut config = PackConfig::new(&root, temp_dir.path().join("out.zip"));
+        config.hash_files = Some(ChecksumAlgorithm::Sha256);
+        let summary = pack_files(&files, &config, |_, _, _| {}).unwrap();
+        // What --verify-against-source runs: no digests, every entry read against its file
+        verify_archive_digests(&files, &config, &[], |_, _, _| {}).unwrap();
+
+        // Digests taken while packing still match the archive, the live file doesn't
+        std::fs::write(root.join("data.txt"), b"modified").unwrap();
+        verify_archive_digests(&files, &config, &summary.digests, |_, _, _| {}).unwrap();
+        let err = verify_archive_digests(&files, &config, &[], |_, _, _| {}).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{}", err);
+    }
+}
@@ -0,0 +1,60 @@
+//! Packing the roots of a `.list` file from the command line.
+
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+use tempfile::tempdir;
+
+/// Runs srcpack in `dir`, returning its standard output.
+fn srcpack(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_srcpack"))
+        .current_dir(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_dry_run_and_freshness_with_root_list() {
+    let temp_dir = tempdir().unwrap();
+    let dir = temp_dir.path();
+    std::fs::create_dir_all(dir.join("api/src")).unwrap();
+    std::fs::create_dir_all(dir.join("web")).unwrap();
+    std::fs::write(dir.join("api/src/main.rs"), b"fn main() {}").unwrap();
+    std::fs::write(dir.join("web/index.html"), b"<html>").unwrap();
+    std::fs::write(dir.join("roots.list"), "api\nweb\n").unwrap();
+
+    // The files of the listed roots are counted and listed
+    let dry_run = srcpack(dir, &["roots.list", "--dry-run"]);
+    assert!(dry_run.contains("api/src/main.rs"), "{}", dry_run);
+    assert!(dry_run.contains("web/index.html"), "{}", dry_run);
+    assert!(dry_run.contains("Total size: 18 B"), "{}", dry_run);
+
+    srcpack(dir, &["roots.list", "-o", "roots.zip", "-q"]);
+    let fresh = srcpack(
+        dir,
+        &["roots.list", "-o", "roots.zip", "--only-newer-than-output"],
+    );
+    assert!(fresh.contains("is up to date"), "{}", fresh);
+
+    // A file of a listed root changed after the archive was written
+    let later = SystemTime::now() + Duration::from_secs(3600);
+    let file = File::options()
+        .write(true)
+        .open(dir.join("web/index.html"))
+        .unwrap();
+    file.set_modified(later).unwrap();
+    let stale = srcpack(
+        dir,
+        &["roots.list", "-o", "roots.zip", "--only-newer-than-output"],
+    );
+    assert!(!stale.contains("is up to date"), "{}", stale);
+}